@@ -0,0 +1,24 @@
+#!/bin/sh
+# Downloads the test ROMs used by tests/blargg.rs and tests/nestest.rs into tests/roms/.
+# Run the tests afterwards with `cargo test -- --ignored`.
+set -eu
+
+ROMS_REPO=https://github.com/christopherpow/nes-test-roms.git
+NESTEST_URL=http://www.qmtpro.com/~nes/misc
+
+roms_dir="$(cd "$(dirname "$0")/.." && pwd)/tests/roms"
+tmp="$(mktemp -d)"
+trap 'rm -rf "$tmp"' EXIT
+
+mkdir -p "$roms_dir"
+git clone --depth 1 "$ROMS_REPO" "$tmp/nes-test-roms"
+for suite in instr_test-v5 ppu_vbl_nmi apu_test ppu_sprite_hit; do
+    rm -rf "$roms_dir/$suite"
+    cp -R "$tmp/nes-test-roms/$suite" "$roms_dir/$suite"
+done
+
+for file in nestest.nes nestest.log; do
+    curl -fsSL -o "$roms_dir/$file" "$NESTEST_URL/$file"
+done
+
+echo "test ROMs are in $roms_dir"
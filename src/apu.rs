@@ -1,4 +1,5 @@
 mod divider;
+mod dmc;
 mod envelope;
 mod frame_counter;
 mod length_counter;
@@ -8,6 +9,8 @@ mod sweep;
 mod timer;
 mod triangle;
 
+use self::dmc::Dmc;
+use self::frame_counter::{FrameCounter, SequencerMode};
 use self::noise::Noise;
 use self::pulse::Pulse;
 use self::pulse::PulseId;
@@ -32,6 +35,8 @@ pub struct Apu {
     pulse2: Pulse,
     triangle: Triangle,
     noise: Noise,
+    dmc: Dmc,
+    frame_counter: FrameCounter,
     clocks: u64,
     buffer: VecDeque<(f32, f32)>,
 }
@@ -50,15 +55,47 @@ impl Apu {
             pulse2: Pulse::new(PulseId::Two),
             triangle: Triangle::new(),
             noise: Noise::new(),
+            dmc: Dmc::new(),
+            frame_counter: FrameCounter::new(),
             clocks: 0,
             buffer: VecDeque::with_capacity(BUFFER_LENGTH),
         }
     }
 
     /// load interface exposed to cpu via bus
-    pub fn load(&self, addr: u16) -> u8 {
-        warn!("Load addr={:#x}", addr);
-        0
+    pub fn load(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x15 => {
+                let status = self.peek(addr);
+                self.frame_counter.clear_irq();
+                status
+            }
+            _ => {
+                warn!("Load addr={:#x}", addr);
+                0
+            }
+        }
+    }
+
+    /// Value `load` would return, without acknowledging the frame IRQ
+    pub fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x15 => {
+                (u8::from(self.dmc.irq()) << 7)
+                    | (u8::from(self.frame_counter.irq()) << 6)
+                    | (u8::from(self.dmc.is_active()) << 4)
+                    | (u8::from(self.noise.is_active()) << 3)
+                    | (u8::from(self.triangle.is_active()) << 2)
+                    | (u8::from(self.pulse2.is_active()) << 1)
+                    | u8::from(self.pulse1.is_active())
+            }
+            _ => 0,
+        }
+    }
+
+    /// true while the frame counter or DMC asserts the cpu IRQ line
+    pub fn irq(&self) -> bool {
+        self.frame_counter.irq() || self.dmc.irq()
     }
 
     /// store interface exposed to cpu via bus
@@ -73,14 +110,22 @@ impl Apu {
                 self.pulse2.store(addr, val >> 1);
                 self.triangle.store(addr, val >> 2);
                 self.noise.store(addr, val >> 3);
+                self.dmc.store(addr, val >> 4);
             }
             0x17 => {
                 self.pulse1.store(addr, val);
                 self.pulse2.store(addr, val);
                 self.triangle.store(addr, val);
                 self.noise.store(addr, val);
+                let mode = if (val & 0x80) != 0 {
+                    SequencerMode::FiveStep
+                } else {
+                    SequencerMode::FourStep
+                };
+                self.frame_counter.set_mode(mode);
+                self.frame_counter.set_irq_inhibit((val & 0x40) != 0);
             }
-            0x11 => {}
+            0x10..=0x13 => self.dmc.store(addr, val),
             _ => {
                 warn!("Store addr={:#x} val={:#x}", addr, val);
             }
@@ -98,12 +143,19 @@ impl Apu {
         (p_out, t_out)
     }
 
+    /// True if DMC has read a sample byte from cpu memory since the last call
+    pub fn take_dmc_fetch(&mut self) -> bool {
+        self.dmc.take_fetch()
+    }
+
     /// Tick 1 CPU clock
     pub fn tick(&mut self) {
         self.pulse1.tick();
         self.pulse2.tick();
         self.triangle.tick();
         self.noise.tick();
+        self.dmc.tick();
+        self.frame_counter.tick();
 
         if sample_index(self.clocks) != sample_index(self.clocks + 1) {
             self.append_buffer(self.sample());
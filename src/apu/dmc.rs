@@ -0,0 +1,123 @@
+// CPU clocks per output bit in NTSC
+static RATE_TABLE: [u16; 0x10] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+#[derive(Clone)]
+/// Delta modulation channel.
+/// Only sample timing is emulated so that the end-of-sample interrupt fires on time
+/// and the cpu is stalled by each sample fetch.
+pub struct Dmc {
+    irq_enabled: bool,
+    loop_flag: bool,
+    rate: u16,
+    sample_length: u16,
+    bytes_remaining: u16,
+    clocks: u16,
+    irq: bool,
+    fetch: bool,
+}
+
+impl Dmc {
+    pub fn new() -> Dmc {
+        Dmc {
+            irq_enabled: false,
+            loop_flag: false,
+            rate: RATE_TABLE[0],
+            sample_length: 1,
+            bytes_remaining: 0,
+            clocks: 0,
+            irq: false,
+            fetch: false,
+        }
+    }
+
+    /// CPU Clock
+    pub fn tick(&mut self) {
+        if self.bytes_remaining == 0 {
+            return;
+        }
+        // a sample byte lasts 8 output bits
+        self.clocks += 1;
+        if self.clocks < self.rate * 8 {
+            return;
+        }
+        self.clocks = 0;
+        // one fetch per sample byte. Hardware fetches the next byte into its buffer
+        // as soon as the buffer empties, this fetches when the byte is used up.
+        self.fetch = true;
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.loop_flag {
+                self.bytes_remaining = self.sample_length;
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    /// true while sample bytes remain
+    pub fn is_active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    /// true if a sample byte is fetched since the last call
+    pub fn take_fetch(&mut self) -> bool {
+        std::mem::replace(&mut self.fetch, false)
+    }
+
+    /// true while the DMC interrupt flag is set
+    pub fn irq(&self) -> bool {
+        self.irq
+    }
+
+    pub fn store(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x10 => {
+                self.irq_enabled = (val & 0x80) != 0;
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+                self.loop_flag = (val & 0x40) != 0;
+                self.rate = RATE_TABLE[(val & 0x0f) as usize];
+            }
+            // output level and sample address don't affect timing
+            0x11 | 0x12 => {}
+            0x13 => {
+                self.sample_length = u16::from(val) * 16 + 1;
+            }
+            0x15 => {
+                self.irq = false;
+                if (val & 0x01) == 0 {
+                    self.bytes_remaining = 0;
+                } else if self.bytes_remaining == 0 {
+                    self.bytes_remaining = self.sample_length;
+                    self.clocks = 0;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fetches_once_per_sample_byte() {
+        let mut dmc = Dmc::new();
+        // fastest rate, 17 bytes
+        dmc.store(0x10, 0x0f);
+        dmc.store(0x13, 0x01);
+        dmc.store(0x15, 0x01);
+        let mut fetches = 0;
+        for _ in 0..17 * 54 * 8 {
+            dmc.tick();
+            fetches += dmc.take_fetch() as u32;
+        }
+        assert_eq!(fetches, 17);
+        assert!(!dmc.is_active());
+        assert!(!dmc.take_fetch());
+    }
+}
@@ -43,6 +43,8 @@ pub struct FrameCounter {
     clocks: u16,
     quarter_frame: bool,
     half_frame: bool,
+    irq_inhibit: bool,
+    irq: bool,
 }
 
 impl FrameCounter {
@@ -52,6 +54,8 @@ impl FrameCounter {
             clocks: 0,
             quarter_frame: false,
             half_frame: false,
+            irq_inhibit: false,
+            irq: false,
         }
     }
 
@@ -63,6 +67,10 @@ impl FrameCounter {
         }
         self.quarter_frame = self.mode.is_quarter_frame(self.clocks);
         self.half_frame = self.mode.is_half_frame(self.clocks);
+        // 4-step sequence raises the frame interrupt at its last step
+        if self.mode == SequencerMode::FourStep && self.clocks == self.mode.period() - 1 {
+            self.irq |= !self.irq_inhibit;
+        }
     }
 
     pub fn is_quarter_frame(&self) -> bool {
@@ -73,6 +81,24 @@ impl FrameCounter {
         self.half_frame
     }
 
+    /// true while the frame interrupt flag is set
+    pub fn irq(&self) -> bool {
+        self.irq
+    }
+
+    /// Reading $4015 clears the frame interrupt flag
+    pub fn clear_irq(&mut self) {
+        self.irq = false;
+    }
+
+    /// Setting the inhibit flag also clears the frame interrupt flag
+    pub fn set_irq_inhibit(&mut self, inhibit: bool) {
+        self.irq_inhibit = inhibit;
+        if inhibit {
+            self.irq = false;
+        }
+    }
+
     pub fn set_mode(&mut self, mode: SequencerMode) {
         self.mode = mode;
         // After 3 or 4 CPU clock cycles, the timer is reset.
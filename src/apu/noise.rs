@@ -94,6 +94,11 @@ impl Noise {
         }
     }
 
+    /// true while the length counter is nonzero
+    pub fn is_active(&self) -> bool {
+        self.length_counter.counter() > 0
+    }
+
     pub fn sample(&self) -> f32 {
         self.volume() * self.sequencer.sample()
     }
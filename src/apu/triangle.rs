@@ -120,6 +120,11 @@ impl Triangle {
         }
     }
 
+    /// true while the length counter is nonzero
+    pub fn is_active(&self) -> bool {
+        self.length_counter.counter() > 0
+    }
+
     pub fn sample(&self) -> f32 {
         self.sequencer.sample()
     }
@@ -0,0 +1,355 @@
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::ops::RangeInclusive;
+use std::process;
+use std::rc::Rc;
+
+use nes::debugger::{
+    Breakpoint, Condition, Debugger, StopReason, WatchKind, WatchTarget, Watchpoint,
+};
+use nes::symbols::SymbolTable;
+use nes::trace::TraceLogger;
+use nes::{Cartridge, Nes};
+
+const HELP: &str = "\
+s [n]                step n instructions
+n                    step over JSR
+o                    step out of subroutine or interrupt handler
+c                    continue until a breakpoint or the frame limit
+sl <scanline>        run until the scanline starts
+b <addr> [after <n>] [if <cond>]   add breakpoint
+db <addr>            delete breakpoint
+w <r|w|rw|x> <addr>[-<addr>] [after <n>] [if <cond>]   watch cpu addresses
+wp <r|w|rw> <reg>[-<reg>] [after <n>] [if <cond>]      watch PPU registers $2000-$2007
+wv <r|w|rw> <addr>[-<addr>] [after <n>] [if <cond>]    watch VRAM accessed through $2007
+dw <id>              delete watchpoint
+l                    list breakpoints and watchpoints
+r                    show registers
+m <addr> [len]       dump memory
+d [addr] [n]         disassemble
+limit <frames>       frame limit for running commands
+reset                press reset button
+sym <file>           load ca65 .dbg or FCEUX .nl symbols
+q                    quit
+
+Conditions are expressions like `A == $40 && [$0300] > 3 && scanline < 20`.
+Names are registers a x y p s pc, flags n v d i z c, scanline, dot, frame and cycles.
+[addr] reads memory. `after <n>` ignores the first n hits.
+Addresses are hexadecimal or symbols like `reset_handler+3`.";
+
+fn read_cartridge(path: &str) -> Result<Cartridge, Box<dyn Error>> {
+    let mut f = File::open(path)?;
+    let mut buffer = Vec::new();
+    f.read_to_end(&mut buffer)?;
+    let cartridge = Cartridge::parse_file(&buffer)?;
+    Ok(cartridge)
+}
+
+fn parse_number(s: &str) -> Result<u16, String> {
+    let parsed = if let Some(digits) = s.strip_prefix('$') {
+        u16::from_str_radix(digits, 16)
+    } else if let Some(digits) = s.strip_prefix("0x") {
+        u16::from_str_radix(digits, 16)
+    } else {
+        s.parse()
+    };
+    parsed.map_err(|_| format!("invalid number: {}", s))
+}
+
+// Addresses are `$` hexadecimal, symbols like `name+3`, or hexadecimal without `$`
+fn parse_addr(symbols: &SymbolTable, s: &str) -> Result<u16, String> {
+    if let Some(digits) = s.strip_prefix('$') {
+        return u16::from_str_radix(digits, 16).map_err(|_| format!("invalid address: {}", s));
+    }
+    symbols
+        .resolve(s)
+        .or_else(|| u16::from_str_radix(s, 16).ok())
+        .ok_or_else(|| format!("invalid address or unknown symbol: {}", s))
+}
+
+fn parse_range(symbols: &SymbolTable, s: &str) -> Result<RangeInclusive<u16>, String> {
+    match s.find('-') {
+        Some(i) => Ok(parse_addr(symbols, &s[..i])?..=parse_addr(symbols, &s[i + 1..])?),
+        None => {
+            let addr = parse_addr(symbols, s)?;
+            Ok(addr..=addr)
+        }
+    }
+}
+
+fn parse_watch_kind(s: &str) -> Result<WatchKind, String> {
+    match s {
+        "r" => Ok(WatchKind::Read),
+        "w" => Ok(WatchKind::Write),
+        "rw" => Ok(WatchKind::ReadWrite),
+        "x" => Ok(WatchKind::Execute),
+        _ => Err(format!("invalid watch kind: {}", s)),
+    }
+}
+
+// Split `<args> [after <n>] [if <cond>]`
+fn parse_condition<'a>(args: &[&'a str]) -> Result<(Vec<&'a str>, Option<Condition>, u64), String> {
+    let (mut args, condition) = match args.iter().position(|w| *w == "if") {
+        Some(i) => {
+            let condition =
+                Condition::parse(&args[i + 1..].join(" ")).map_err(|e| e.to_string())?;
+            (args[..i].to_vec(), Some(condition))
+        }
+        None => (args.to_vec(), None),
+    };
+    let mut ignore_count = 0;
+    if let Some(i) = args.iter().position(|w| *w == "after") {
+        let count = args.get(i + 1).ok_or("expected hit count")?;
+        ignore_count = count
+            .parse()
+            .map_err(|_| format!("invalid hit count: {}", count))?;
+        args.truncate(i);
+    }
+    Ok((args, condition, ignore_count))
+}
+
+fn parse_breakpoint(symbols: &SymbolTable, args: &[&str]) -> Result<Breakpoint, String> {
+    let (args, condition, ignore_count) = parse_condition(args)?;
+    if args.len() != 1 {
+        return Err("expected <addr>".to_string());
+    }
+    let mut breakpoint = Breakpoint::new(parse_addr(symbols, args[0])?);
+    breakpoint.condition = condition;
+    breakpoint.ignore_count = ignore_count;
+    Ok(breakpoint)
+}
+
+fn parse_watchpoint(
+    symbols: &SymbolTable,
+    target: WatchTarget,
+    args: &[&str],
+) -> Result<Watchpoint, String> {
+    let (args, condition, ignore_count) = parse_condition(args)?;
+    if args.len() != 2 {
+        return Err("expected <kind> <range>".to_string());
+    }
+    let kind = parse_watch_kind(args[0])?;
+    if kind == WatchKind::Execute && target != WatchTarget::Cpu {
+        return Err("only cpu addresses can be executed".to_string());
+    }
+    let mut range = parse_range(symbols, args[1])?;
+    if target == WatchTarget::PpuRegister {
+        // accept both register numbers and addresses
+        let (start, end) = (*range.start(), *range.end());
+        range = (0x2000 | (start & 0x07))..=(0x2000 | (end & 0x07));
+    }
+    let mut watchpoint = Watchpoint::new(target, kind, range);
+    watchpoint.condition = condition;
+    watchpoint.ignore_count = ignore_count;
+    Ok(watchpoint)
+}
+
+fn current_line(debugger: &Debugger) -> String {
+    let nes = debugger.nes();
+    let reg = nes.cpu_registers();
+    let (scanline, dot) = nes.ppu_position();
+    let inst = debugger.disassemble(reg.pc);
+    let label = |addr| debugger.label(addr);
+    TraceLogger::format_line_with(&inst, &label, &reg, nes, scanline, dot, nes.cycles())
+}
+
+fn print_current(debugger: &Debugger) {
+    if let Some(label) = debugger.label(debugger.nes().cpu_registers().pc) {
+        println!("{}:", label);
+    }
+    println!("{}", current_line(debugger));
+}
+
+fn print_stop(debugger: &Debugger, reason: StopReason) {
+    if reason != StopReason::Step {
+        println!("stopped: {}", reason);
+    }
+    print_current(debugger);
+}
+
+fn dump_memory(debugger: &Debugger, start: u16, len: u16) {
+    for row in (0..len).step_by(16) {
+        let addr = start.wrapping_add(row);
+        let bytes = (0..16.min(len - row))
+            .map(|i| format!("{:02X}", debugger.peek(addr.wrapping_add(i))))
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!("{:04X}  {}", addr, bytes);
+    }
+}
+
+fn disassemble(debugger: &Debugger, start: u16, count: u16) {
+    let mut addr = start;
+    for _ in 0..count {
+        let inst = debugger.disassemble(addr);
+        // `name+offset` labels are only for operands
+        let symbol = debugger.symbols().lookup(addr, debugger.nes());
+        if let Some((symbol, 0)) = symbol {
+            println!("{}:", symbol.name);
+        }
+        let marker = if debugger.breakpoints().any(|b| b.addr == addr) {
+            '*'
+        } else {
+            ' '
+        };
+        println!(
+            "{}{:04X}  {}",
+            marker,
+            addr,
+            debugger.format_instruction(&inst)
+        );
+        addr = inst.next_addr();
+    }
+}
+
+fn list(debugger: &Debugger) {
+    for breakpoint in debugger.breakpoints() {
+        println!("breakpoint {}", breakpoint);
+    }
+    for (id, watchpoint) in debugger.watchpoints() {
+        println!("watchpoint #{} {}", id, watchpoint);
+    }
+}
+
+// Returns false to quit
+fn execute(debugger: &mut Debugger, line: &str) -> Result<bool, String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    let (command, args) = match words.split_first() {
+        Some((command, args)) => (*command, args),
+        None => return Ok(true),
+    };
+    let symbols = debugger.symbols().clone();
+    match command {
+        "s" => {
+            let count = args.first().map_or(Ok(1), |s| parse_number(s))?;
+            let mut reason = StopReason::Step;
+            for _ in 0..count {
+                reason = debugger.step();
+                if reason != StopReason::Step {
+                    break;
+                }
+            }
+            print_stop(debugger, reason);
+        }
+        "n" => {
+            let reason = debugger.step_over();
+            print_stop(debugger, reason);
+        }
+        "o" => {
+            let reason = debugger.step_out();
+            print_stop(debugger, reason);
+        }
+        "c" => {
+            let reason = debugger.resume();
+            print_stop(debugger, reason);
+        }
+        "sl" => {
+            let scanline = parse_number(args.first().ok_or("expected scanline")?)?;
+            let reason = debugger.run_to_scanline(scanline);
+            print_stop(debugger, reason);
+        }
+        "b" => debugger.add_breakpoint(parse_breakpoint(&symbols, args)?),
+        "db" => {
+            let addr = parse_addr(&symbols, args.first().ok_or("expected address")?)?;
+            if !debugger.remove_breakpoint(addr) {
+                return Err(format!("no breakpoint at ${:04X}", addr));
+            }
+        }
+        "w" | "wp" | "wv" => {
+            let target = match command {
+                "w" => WatchTarget::Cpu,
+                "wp" => WatchTarget::PpuRegister,
+                _ => WatchTarget::Vram,
+            };
+            let id = debugger.add_watchpoint(parse_watchpoint(&symbols, target, args)?);
+            println!("watchpoint #{}", id);
+        }
+        "dw" => {
+            let id = parse_number(args.first().ok_or("expected id")?)?;
+            if !debugger.remove_watchpoint(usize::from(id)) {
+                return Err(format!("no watchpoint #{}", id));
+            }
+        }
+        "l" => list(debugger),
+        "r" => print_current(debugger),
+        "m" => {
+            let addr = parse_addr(&symbols, args.first().ok_or("expected address")?)?;
+            let len = args.get(1).map_or(Ok(0x40), |s| parse_number(s))?;
+            dump_memory(debugger, addr, len);
+        }
+        "d" => {
+            let pc = debugger.nes().cpu_registers().pc;
+            let addr = args.first().map_or(Ok(pc), |s| parse_addr(&symbols, s))?;
+            let count = args.get(1).map_or(Ok(10), |s| parse_number(s))?;
+            disassemble(debugger, addr, count);
+        }
+        "limit" => {
+            let frames = parse_number(args.first().ok_or("expected frames")?)?;
+            debugger.set_frame_limit(u64::from(frames));
+        }
+        "reset" => {
+            debugger.nes_mut().reset();
+            print_current(debugger);
+        }
+        "sym" => {
+            let path = args.first().ok_or("expected symbol file")?;
+            let mut symbols = symbols;
+            symbols.load_file(path).map_err(|e| e.to_string())?;
+            println!("{} symbols", symbols.symbols().len());
+            debugger.set_symbols(Rc::new(symbols));
+        }
+        "q" => return Ok(false),
+        "h" | "help" => println!("{}", HELP),
+        _ => return Err(format!("unknown command: {} (h for help)", command)),
+    }
+    Ok(true)
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    if args.len() < 2 {
+        eprintln!("usage: {} path-to-rom-file [symbol-file...]", args[0]);
+        process::exit(2);
+    }
+    let cartridge = match read_cartridge(&args[1]) {
+        Ok(cartridge) => cartridge,
+        Err(e) => {
+            eprintln!("{}: {}", args[1], e);
+            process::exit(1);
+        }
+    };
+
+    let mut symbols = SymbolTable::new();
+    for path in &args[2..] {
+        if let Err(e) = symbols.load_file(path) {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    }
+
+    let mut debugger = Debugger::new(Nes::with_cartridge(cartridge));
+    debugger.set_symbols(Rc::new(symbols));
+    print_current(&debugger);
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        match execute(&mut debugger, &line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::process;
+use std::rc::Rc;
+
+use nes::profiler::Profiler;
+use nes::symbols::SymbolTable;
+use nes::{Cartridge, KeyState, Nes};
+
+const USAGE: &str = "\
+usage: profile path-to-rom-file [--frames <n>] [--json] [symbol-file...]
+
+Runs the ROM without input and reports cpu cycles per routine.
+--frames <n>   number of frames to run (default 600)
+--json         write the report in JSON";
+
+fn read_cartridge(path: &str) -> Result<Cartridge, Box<dyn Error>> {
+    let mut f = File::open(path)?;
+    let mut buffer = Vec::new();
+    f.read_to_end(&mut buffer)?;
+    let cartridge = Cartridge::parse_file(&buffer)?;
+    Ok(cartridge)
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let path = args.next().ok_or(USAGE)?;
+    let mut frames = 600;
+    let mut json = false;
+    let mut symbols = SymbolTable::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => {
+                let n = args.next().ok_or("--frames needs a number")?;
+                frames = n.parse().map_err(|_| format!("invalid number: {}", n))?;
+            }
+            "--json" => json = true,
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ => symbols
+                .load_file(&arg)
+                .map_err(|e| format!("{}: {}", arg, e))?,
+        }
+    }
+
+    let cartridge = read_cartridge(&path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut nes = Nes::with_cartridge(cartridge);
+    let mut profiler = Profiler::new();
+    profiler.set_symbols(Rc::new(symbols));
+    nes.set_profiler(profiler);
+    for _ in 0..frames {
+        nes.next_frame(KeyState::default());
+    }
+
+    let profiler = nes.take_profiler().expect("profiler is set");
+    let stdout = io::stdout();
+    if json {
+        profiler.write_json(stdout.lock())?;
+    } else {
+        profiler.write_text(stdout.lock())?;
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,56 @@
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::prelude::*;
+use std::process;
+
+use nes::test_rom::TestRomRunner;
+use nes::Cartridge;
+
+// 1 minute
+const MAX_FRAMES: u64 = 60 * 60;
+
+fn read_cartridge(path: &str) -> Result<Cartridge, Box<dyn Error>> {
+    let mut f = File::open(path)?;
+    let mut buffer = Vec::new();
+    f.read_to_end(&mut buffer)?;
+    let cartridge = Cartridge::parse_file(&buffer)?;
+    Ok(cartridge)
+}
+
+fn run(path: &str) -> Result<bool, Box<dyn Error>> {
+    let result = TestRomRunner::new(read_cartridge(path)?, MAX_FRAMES).run()?;
+    if result.passed() {
+        println!("PASS {}", path);
+    } else {
+        println!("FAIL {} (code {})", path, result.code);
+    }
+    if !result.message.is_empty() {
+        println!("{}", result.message);
+    }
+    Ok(result.passed())
+}
+
+fn main() {
+    let paths = env::args().skip(1).collect::<Vec<_>>();
+    if paths.is_empty() {
+        eprintln!("usage: test_rom path-to-rom-file...");
+        process::exit(2);
+    }
+
+    let mut failed = 0;
+    for path in &paths {
+        match run(path) {
+            Ok(true) => {}
+            Ok(false) => failed += 1,
+            Err(e) => {
+                println!("ERROR {}: {}", path, e);
+                failed += 1;
+            }
+        }
+    }
+    println!("{}/{} passed", paths.len() - failed, paths.len());
+    if failed > 0 {
+        process::exit(1);
+    }
+}
@@ -1,29 +1,174 @@
 use crate::apu::Apu;
 use crate::cartridge::Cartridge;
+use crate::cdl;
 use crate::dma::Dma;
 use crate::joypad::JoyPad;
 use crate::memory::Memory;
 use crate::ppu::Ppu;
+use std::rc::Rc;
+
+// cpu clocks halted by a DMC sample fetch. Hardware takes 1-3 fewer
+// when the fetch lands on a write or during OAM DMA.
+const DMC_FETCH_CYCLES: u32 = 4;
+
+/// Kind of cpu bus access
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    /// load from address
+    Read,
+    /// store into address
+    Write,
+}
+
+/// Access to an address where nothing responds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnmappedAccess {
+    /// cpu address
+    pub addr: u16,
+    /// load or store
+    pub kind: AccessKind,
+    /// open bus value for reads, written value for writes
+    pub value: u8,
+}
+
+/// Address space of a recorded access
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressSpace {
+    /// cpu address bus
+    Cpu,
+    /// ppu address bus accessed by the cpu through $2007
+    Vram,
+}
+
+/// Access recorded while an access log is attached to the bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusAccess {
+    /// bus the address belongs to
+    pub space: AddressSpace,
+    /// address on the bus
+    pub addr: u16,
+    /// load or store
+    pub kind: AccessKind,
+    /// value seen by the cpu
+    pub value: u8,
+}
+
+#[derive(Clone, Default)]
+/// Last value driven on the cpu data bus.
+/// Reads from unmapped addresses return it, because nothing drives the bus.
+pub struct OpenBus {
+    value: u8,
+    strict: Option<Rc<dyn Fn(UnmappedAccess)>>,
+}
+
+impl OpenBus {
+    /// Create new instance
+    pub fn new() -> OpenBus {
+        OpenBus::default()
+    }
+
+    /// Report unmapped accesses to the callback
+    pub fn set_strict_mode(&mut self, callback: Rc<dyn Fn(UnmappedAccess)>) {
+        self.strict = Some(callback);
+    }
+
+    /// Silently ignore unmapped accesses
+    pub fn clear_strict_mode(&mut self) {
+        self.strict = None;
+    }
+
+    /// Value currently left on the bus
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+
+    fn unmapped(&self, addr: u16, kind: AccessKind, value: u8) {
+        if let Some(callback) = &self.strict {
+            callback(UnmappedAccess { addr, kind, value });
+        }
+    }
+}
+
+/// Read-only memory map for cpu. Loads through it have no side effects.
+pub struct BusView<'a> {
+    cartridge: &'a Cartridge,
+    wram: &'a Memory,
+    ppu: &'a Ppu,
+    apu: &'a Apu,
+    joypad: &'a JoyPad,
+    open_bus: &'a OpenBus,
+}
+
+impl<'a> BusView<'a> {
+    /// Create view from nesessary components
+    pub fn new(
+        cartridge: &'a Cartridge,
+        wram: &'a Memory,
+        ppu: &'a Ppu,
+        apu: &'a Apu,
+        joypad: &'a JoyPad,
+        open_bus: &'a OpenBus,
+    ) -> BusView<'a> {
+        BusView {
+            cartridge,
+            wram,
+            ppu,
+            apu,
+            joypad,
+            open_bus,
+        }
+    }
+
+    /// Value `Bus::load` would return, without side effects
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.decode(addr).unwrap_or(self.open_bus.value)
+    }
+
+    // Memory map shared with `Bus::read`. Returns None if nothing drives the bus.
+    fn decode(&self, addr: u16) -> Option<u8> {
+        let open_bus = self.open_bus.value;
+        let val = match addr {
+            0x0000..=0x1fff => self.wram.load(addr & 0x07ff),
+            0x2000..=0x3fff => self.ppu.peek((addr - 0x2000) & 0x7, self.cartridge),
+            0x4015 => self.apu.peek(addr - 0x4000),
+            // write-only registers
+            0x4000..=0x4014 => open_bus,
+            // controller port drives only the low bits
+            0x4016 => (open_bus & 0xe0) | self.joypad.peek(),
+            0x4017 => open_bus & 0xe0, // TODO: implement joy pad 2
+            0x4018..=0x401f => return None,
+            0x4020..=0xffff => return self.cartridge.cpu_load(addr),
+        };
+        Some(val)
+    }
+}
 
 /// Memory map for cpu
 pub struct Bus<'a> {
-    cartridge: &'a Cartridge,
+    cartridge: &'a mut Cartridge,
     wram: &'a mut Memory,
     ppu: &'a mut Ppu,
     apu: &'a mut Apu,
     joypad: &'a mut JoyPad,
     dma: &'a mut Dma,
+    open_bus: &'a mut OpenBus,
+    access_log: Option<&'a mut Vec<BusAccess>>,
+    indirect_data: bool,
+    start_cycles: u64,
+    cycles: u64,
+    stalled_cycles: u64,
 }
 
 impl<'a> Bus<'a> {
     /// Create bus from nesessary components
     pub fn new(
-        cartridge: &'a Cartridge,
+        cartridge: &'a mut Cartridge,
         wram: &'a mut Memory,
         ppu: &'a mut Ppu,
         apu: &'a mut Apu,
         joypad: &'a mut JoyPad,
         dma: &'a mut Dma,
+        open_bus: &'a mut OpenBus,
     ) -> Bus<'a> {
         Bus {
             cartridge,
@@ -32,54 +177,219 @@ impl<'a> Bus<'a> {
             apu,
             joypad,
             dma,
+            open_bus,
+            access_log: None,
+            indirect_data: false,
+            start_cycles: 0,
+            cycles: 0,
+            stalled_cycles: 0,
         }
     }
 
-    /// Load 1 byte from address
-    pub fn load(&mut self, addr: u16) -> u8 {
+    /// Set the number of cpu clocks before the bus is created
+    pub fn set_start_cycles(&mut self, cycles: u64) {
+        self.start_cycles = cycles;
+    }
+
+    /// Record every load and store into the log
+    pub fn set_access_log(&mut self, log: &'a mut Vec<BusAccess>) {
+        self.access_log = Some(log);
+    }
+
+    fn record(&mut self, space: AddressSpace, addr: u16, kind: AccessKind, value: u8) {
+        if let Some(log) = &mut self.access_log {
+            log.push(BusAccess {
+                space,
+                addr,
+                kind,
+                value,
+            });
+        }
+    }
+
+    // VRAM address accessed if the cpu address is $2007 or its mirror
+    fn ppudata_vram_addr(&self, addr: u16) -> Option<u16> {
         match addr {
-            0x0000...0x1fff => self.wram.load(addr & 0x07ff),
-            0x2000...0x3fff => self.ppu.load((addr - 0x2000) & 0x7),
-            0x4000...0x4015 | 0x4018...0x401f => self.apu.load(addr - 0x4000),
-            0x4016 => self.joypad.load(),
-            0x4017 => 0, // TODO: implement joy pad 2
-            0x4020...0x7fff => panic!("not implemented to load {:#x}", addr),
-            0x8000...0xffff => self.cartridge.prg_rom[(addr - 0x8000) as usize],
+            0x2000..=0x3fff if addr & 0x07 == 0x07 => Some(self.ppu.vram_addr()),
+            _ => None,
+        }
+    }
+
+    fn view(&self) -> BusView<'_> {
+        BusView::new(
+            self.cartridge,
+            self.wram,
+            self.ppu,
+            self.apu,
+            self.joypad,
+            self.open_bus,
+        )
+    }
+
+    /// Value `load` would return, without side effects
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.view().peek(addr)
+    }
+
+    /// Number of cpu clocks since the bus is created
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Number of cpu clocks halted by DMC sample fetches, included in `cycles`
+    pub fn stalled_cycles(&self) -> u64 {
+        self.stalled_cycles
+    }
+
+    /// True if PPU has requested NMI since the last call
+    pub fn take_vblank_nmi(&mut self) -> bool {
+        self.ppu.take_nmi()
+    }
+
+    /// True while any device asserts the cpu IRQ line
+    pub fn irq(&self) -> bool {
+        self.apu.irq() || self.cartridge.irq()
+    }
+
+    /// Run OAM DMA requested by $4014. CPU is halted while the transfer runs.
+    pub fn run_dma(&mut self) {
+        if let Some(high) = self.dma.take_request() {
+            // halt cycle, then an alignment cycle if DMA would start reading on an odd cycle
+            self.tick();
+            if (self.start_cycles + self.cycles) & 1 == 1 {
+                self.tick();
+            }
+            for low in 0..0x100 {
+                let val = self.load((u16::from(high) << 8) | low);
+                self.tick();
+                self.ppu.write_oam(val);
+            }
+        }
+    }
+
+    // Advance PPU and APU by 1 cpu clock, and more while DMC halts the cpu
+    fn tick(&mut self) {
+        self.clock();
+        if self.apu.take_dmc_fetch() {
+            for _ in 0..DMC_FETCH_CYCLES {
+                self.clock();
+            }
+            self.stalled_cycles += u64::from(DMC_FETCH_CYCLES);
+        }
+    }
+
+    fn clock(&mut self) {
+        self.cycles += 1;
+        for _ in 0..3 {
+            self.ppu.exec(self.cartridge);
         }
+        self.apu.tick();
+    }
+
+    /// Mark the following loads as made through a pointer, for the Code/Data Log
+    pub fn set_indirect_data(&mut self, indirect: bool) {
+        self.indirect_data = indirect;
+    }
+
+    /// Flag the destination of an indirect jump in the Code/Data Log
+    pub fn log_indirect_jump(&mut self, target: u16) {
+        self.cartridge.log_prg(target, cdl::PRG_INDIRECT_CODE);
+    }
+
+    /// Load 1 byte of data from address
+    pub fn load(&mut self, addr: u16) -> u8 {
+        let val = self.read(addr);
+        let flags = if self.indirect_data {
+            cdl::PRG_DATA | cdl::PRG_INDIRECT_DATA
+        } else {
+            cdl::PRG_DATA
+        };
+        self.cartridge.log_prg(addr, flags);
+        val
+    }
+
+    /// Load 1 byte of opcode or operand from address
+    pub fn fetch(&mut self, addr: u16) -> u8 {
+        let val = self.read(addr);
+        self.cartridge.log_prg(addr, cdl::PRG_CODE);
+        val
+    }
+
+    /// Load 1 byte whose value is discarded by the cpu
+    pub fn dummy_load(&mut self, addr: u16) -> u8 {
+        self.read(addr)
+    }
+
+    fn read(&mut self, addr: u16) -> u8 {
+        self.tick();
+        let vram_addr = self.ppudata_vram_addr(addr);
+        let open_bus = self.open_bus.value;
+        // registers whose reads change state, the rest are decoded by `BusView`
+        let val = match addr {
+            // $2002, $2004 and $2007 also refresh the PPU I/O latch
+            0x2000..=0x3fff if matches!(addr & 0x07, 0x02 | 0x04 | 0x07) => {
+                self.ppu.load((addr - 0x2000) & 0x7, self.cartridge)
+            }
+            0x4015 => self.apu.load(addr - 0x4000),
+            0x4016 => (open_bus & 0xe0) | self.joypad.load(),
+            _ => match self.view().decode(addr) {
+                Some(val) => val,
+                None => {
+                    self.open_bus.unmapped(addr, AccessKind::Read, open_bus);
+                    open_bus
+                }
+            },
+        };
+        self.open_bus.value = val;
+        self.record(AddressSpace::Cpu, addr, AccessKind::Read, val);
+        if let Some(vram_addr) = vram_addr {
+            self.record(AddressSpace::Vram, vram_addr, AccessKind::Read, val);
+        }
+        val
     }
 
     /// Store 1 byte value into address
     pub fn store(&mut self, addr: u16, val: u8) {
+        self.tick();
+        self.open_bus.value = val;
+        self.record(AddressSpace::Cpu, addr, AccessKind::Write, val);
+        if let Some(vram_addr) = self.ppudata_vram_addr(addr) {
+            self.record(AddressSpace::Vram, vram_addr, AccessKind::Write, val);
+        }
         match addr {
-            0x0000...0x1fff => self.wram.store(addr & 0x7ff, val), // TODO: correct for mirror mode?
-            0x2000...0x3fff => self.ppu.store((addr - 0x2000) & 0x7, val),
+            0x0000..=0x1fff => self.wram.store(addr & 0x7ff, val), // TODO: correct for mirror mode?
+            0x2000..=0x3fff => self.ppu.store((addr - 0x2000) & 0x7, val, self.cartridge),
             0x4014 => self.dma.write(val),
-            0x4000...0x4013 | 0x4015 | 0x4018...0x401f => self.apu.store(addr - 0x4000, val),
+            0x4000..=0x4013 | 0x4015 | 0x4017 => self.apu.store(addr - 0x4000, val),
             0x4016 => self.joypad.store(val),
-            0x4017 => {} // TODO: implement joy pad 2
-            0x4020...0xffff => panic!("not implemented to set {:#x} at address {:#x}", val, addr),
+            0x4018..=0x401f => self.open_bus.unmapped(addr, AccessKind::Write, val),
+            0x4020..=0xffff => {
+                let cpu_cycle = self.start_cycles + self.cycles;
+                if self.cartridge.cpu_store(addr, val, cpu_cycle) {
+                    // mapper registers can switch nametable mirroring
+                    self.ppu.sync_mirroring(self.cartridge);
+                } else {
+                    self.open_bus.unmapped(addr, AccessKind::Write, val);
+                }
+            }
         };
     }
 
-    /// Load 2 bytes from address
+    /// Load 2 bytes of data from address
     pub fn load_w(&mut self, addr: u16) -> u16 {
-        // From nestest, it seems page boundary wraps next address.
-        let next_addr = if addr == 0xff || addr == 0x02ff {
-            addr ^ 0xff
-        } else {
-            addr + 1
-        };
+        let next_addr = addr.wrapping_add(1);
         u16::from(self.load(addr)) | (u16::from(self.load(next_addr)) << 8)
     }
 
+    /// Load 2 bytes of operand from address
+    pub fn fetch_w(&mut self, addr: u16) -> u16 {
+        let next_addr = addr.wrapping_add(1);
+        u16::from(self.fetch(addr)) | (u16::from(self.fetch(next_addr)) << 8)
+    }
+
     /// Store 2 bytes value into address with little endian.
     pub fn store_w(&mut self, addr: u16, val: u16) {
-        // From nestest, it seems page boundary wraps next address.
-        let next_addr = if addr == 0xff || addr == 0x02ff {
-            addr ^ 0xff
-        } else {
-            addr + 1
-        };
+        let next_addr = addr.wrapping_add(1);
         self.store(addr, (val & 0xff) as u8);
         self.store(next_addr, (val >> 8) as u8);
     }
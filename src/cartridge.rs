@@ -1,58 +1,221 @@
-use log::info;
+mod error;
+mod header;
 
-const PRG_ROM_PAGE_UNIT: usize = 16 * 1024;
-const CHR_ROM_PAGE_UNIT: usize = 8 * 1024;
+pub use self::error::CartridgeError;
+pub use self::header::{CartridgeHeader, ConsoleType, HeaderFormat, TvSystem};
+
+use self::header::{HEADER_LENGTH, TRAINER_LENGTH};
+use crate::cdl::CodeDataLog;
+use crate::mapper::{self, Mapper, Mirroring};
+use log::{info, warn};
 
 #[derive(Clone)]
 /// NES cartridge with two memory chips.
 pub struct Cartridge {
+    /// parsed file header
+    pub header: CartridgeHeader,
     /// rom connected to cpu
     pub prg_rom: Vec<u8>,
     /// rom connected to ppu
     pub chr_rom: Vec<u8>,
+    mapper: Box<dyn Mapper>,
+    code_data_log: Option<CodeDataLog>,
 }
 
 impl Cartridge {
-    /// Construct from iNES file format
-    pub fn parse_file(d: &[u8]) -> Result<Cartridge, String> {
-        let mut it = d.iter().cloned();
-        let magic = read_bytes(&mut it, 4)?;
-        if magic != b"NES\x1a" {
-            return Err("damaged header".into());
-        }
-        let prg_rom_pages = read_byte(&mut it)? as usize;
-        let chr_rom_pages = read_byte(&mut it)? as usize;
+    /// Construct from iNES or NES 2.0 file format
+    pub fn parse_file(d: &[u8]) -> Result<Cartridge, CartridgeError> {
+        let raw_header =
+            read_bytes(d, 0, HEADER_LENGTH).ok_or(CartridgeError::TruncatedHeader {
+                expected: HEADER_LENGTH,
+                actual: d.len(),
+            })?;
+        if &raw_header[0..4] != b"NES\x1a" {
+            let mut found = [0; 4];
+            found.copy_from_slice(&raw_header[0..4]);
+            return Err(CartridgeError::BadMagic { found });
+        }
+        let mut header_bytes = [0; HEADER_LENGTH];
+        header_bytes.copy_from_slice(raw_header);
+        let header = CartridgeHeader::parse(&header_bytes)?;
         info!(
-            "Loading {} prg pages and {} chr pages",
-            prg_rom_pages, chr_rom_pages
+            "Loading {} bytes prg and {} bytes chr (mapper {})",
+            header.prg_rom_size, header.chr_rom_size, header.mapper
         );
-        let _ = read_bytes(&mut it, 10)?;
-        let mut prg_rom = read_bytes(&mut it, prg_rom_pages * PRG_ROM_PAGE_UNIT)?;
 
-        // TODO: this is workaround to run nestest.
-        // See https://github.com/PyAndy/Py3NES/issues/1#issuecomment-224071286
-        if prg_rom_pages == 1 {
-            let copy = prg_rom.clone();
-            prg_rom.extend(copy);
+        let mut offset = HEADER_LENGTH;
+        if header.trainer {
+            read_bytes(d, offset, TRAINER_LENGTH).ok_or(CartridgeError::BadTrainer {
+                offset,
+                expected: TRAINER_LENGTH,
+                actual: remaining_bytes(d, offset),
+            })?;
+            offset += TRAINER_LENGTH;
+        }
+
+        if header.prg_rom_size == 0 {
+            return Err(CartridgeError::EmptyPrgRom);
         }
+        let prg_rom = read_bytes(d, offset, header.prg_rom_size)
+            .ok_or(CartridgeError::TruncatedPrg {
+                offset,
+                expected: header.prg_rom_size,
+                actual: remaining_bytes(d, offset),
+            })?
+            .to_vec();
+        offset += header.prg_rom_size;
+
+        let chr_rom = read_bytes(d, offset, header.chr_rom_size)
+            .ok_or(CartridgeError::TruncatedChr {
+                offset,
+                expected: header.chr_rom_size,
+                actual: remaining_bytes(d, offset),
+            })?
+            .to_vec();
 
-        let chr_rom = read_bytes(&mut it, chr_rom_pages * CHR_ROM_PAGE_UNIT)?;
-        Ok(Cartridge { prg_rom, chr_rom })
+        let mapper = mapper::create(&header, prg_rom.clone(), chr_rom.clone())?;
+        Ok(Cartridge {
+            header,
+            prg_rom,
+            chr_rom,
+            mapper,
+            code_data_log: None,
+        })
+    }
+
+    /// Load 1 byte from cpu address space ($4020-$FFFF)
+    pub fn cpu_load(&self, addr: u16) -> Option<u8> {
+        self.mapper.cpu_load(addr)
+    }
+
+    /// Offset in PRG ROM currently mapped at the cpu address.
+    /// None if the address is not mapped to PRG ROM.
+    pub fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.mapper.prg_rom_offset(addr)
+    }
+
+    /// Offset in CHR ROM currently mapped at the ppu address.
+    /// None if the address is mapped to CHR RAM.
+    pub fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.mapper.chr_rom_offset(addr)
     }
-}
 
-fn read_byte(iter: &mut Iterator<Item = u8>) -> Result<u8, String> {
-    match iter.next() {
-        Some(val) => Ok(val),
-        None => Err("unexpected end of file".into()),
+    /// Store 1 byte into cpu address space ($4020-$FFFF).
+    /// `cpu_cycle` counts cpu clocks since power on.
+    /// Returns false if nothing on the board received the write.
+    pub fn cpu_store(&mut self, addr: u16, val: u8, cpu_cycle: u64) -> bool {
+        self.mapper.cpu_store(addr, val, cpu_cycle)
+    }
+
+    /// Load 1 byte from ppu address space ($0000-$1FFF)
+    pub fn ppu_load(&self, addr: u16) -> u8 {
+        self.mapper.ppu_load(addr)
+    }
+
+    /// Store 1 byte into ppu address space ($0000-$1FFF)
+    pub fn ppu_store(&mut self, addr: u16, val: u8) {
+        self.mapper.ppu_store(addr, val)
+    }
+
+    /// Nametable mirroring selected by the board.
+    /// Four-screen VRAM overrides whatever the mapper selects.
+    pub fn mirroring(&self) -> Mirroring {
+        if self.header.four_screen {
+            Mirroring::FourScreen
+        } else {
+            self.mapper.mirroring()
+        }
+    }
+
+    /// Battery-backed PRG RAM to persist across power cycles.
+    /// None if the cartridge has no battery.
+    pub fn save_data(&self) -> Option<&[u8]> {
+        let ram = self.mapper.prg_ram();
+        if self.header.battery && !ram.is_empty() {
+            Some(ram)
+        } else {
+            None
+        }
+    }
+
+    /// Restore PRG RAM from data previously returned by `save_data`
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        let ram = self.mapper.prg_ram_mut();
+        if ram.len() != data.len() {
+            warn!(
+                "save data is {} bytes but PRG RAM is {} bytes",
+                data.len(),
+                ram.len()
+            );
+        }
+        let len = ram.len().min(data.len());
+        ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    /// Let the board observe the ppu bus when A12 changes
+    pub fn notify_ppu_address(&mut self, addr: u16, ppu_cycle: u64) {
+        self.mapper.notify_ppu_address(addr, ppu_cycle)
+    }
+
+    /// Start recording ROM accesses into a new Code/Data Log.
+    /// A log already running is kept.
+    pub fn start_code_data_log(&mut self) {
+        if self.code_data_log.is_none() {
+            let log = CodeDataLog::new(self.prg_rom.len(), self.chr_rom.len());
+            self.code_data_log = Some(log);
+        }
+    }
+
+    /// Code/Data Log recorded since `start_code_data_log`
+    pub fn code_data_log(&self) -> Option<&CodeDataLog> {
+        self.code_data_log.as_ref()
+    }
+
+    /// Stop recording and return the log
+    pub fn take_code_data_log(&mut self) -> Option<CodeDataLog> {
+        self.code_data_log.take()
+    }
+
+    /// Flag the PRG ROM byte mapped at the cpu address, if the log is running
+    pub fn log_prg(&mut self, addr: u16, flags: u8) {
+        if let Some(log) = &mut self.code_data_log {
+            if let Some(offset) = self.mapper.prg_rom_offset(addr) {
+                log.log_prg(offset, addr, flags);
+            }
+        }
+    }
+
+    /// Flag the CHR ROM byte mapped at the ppu address, if the log is running
+    pub fn log_chr(&mut self, addr: u16, flags: u8) {
+        if let Some(log) = &mut self.code_data_log {
+            if let Some(offset) = self.mapper.chr_rom_offset(addr) {
+                log.log_chr(offset, flags);
+            }
+        }
+    }
+
+    /// True while the board asserts the cpu IRQ line
+    pub fn irq(&self) -> bool {
+        self.mapper.irq()
     }
 }
 
-fn read_bytes(iter: &mut Iterator<Item = u8>, bytes: usize) -> Result<Vec<u8>, String> {
-    let buf: Vec<_> = iter.take(bytes).collect();
-    if buf.len() < bytes {
-        Err("unexpected end of file".into())
-    } else {
-        Ok(buf)
+fn read_bytes(d: &[u8], offset: usize, bytes: usize) -> Option<&[u8]> {
+    d.get(offset..offset.checked_add(bytes)?)
+}
+
+fn remaining_bytes(d: &[u8], offset: usize) -> usize {
+    d.len().saturating_sub(offset)
+}
+
+#[cfg(test)]
+impl Cartridge {
+    // NROM cartridge with 32KB PRG ROM and blank CHR ROM for unit tests
+    pub(crate) fn nrom(prg_rom: &[u8]) -> Cartridge {
+        assert_eq!(prg_rom.len(), 0x8000);
+        let mut rom = vec![0x4e, 0x45, 0x53, 0x1a, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        rom.extend_from_slice(prg_rom);
+        rom.extend(vec![0; 0x2000]);
+        Cartridge::parse_file(&rom).unwrap()
     }
 }
@@ -0,0 +1,121 @@
+use std::error::Error;
+use std::fmt;
+
+/// Error on loading a ROM file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CartridgeError {
+    /// file is shorter than the 16 bytes header
+    TruncatedHeader {
+        /// header length
+        expected: usize,
+        /// file length
+        actual: usize,
+    },
+    /// first 4 bytes are not "NES\x1a"
+    BadMagic {
+        /// first 4 bytes in the file
+        found: [u8; 4],
+    },
+    /// header says trainer is present but the file ends in the middle of it
+    BadTrainer {
+        /// file offset where trainer starts
+        offset: usize,
+        /// trainer length
+        expected: usize,
+        /// bytes available from the offset
+        actual: usize,
+    },
+    /// header says PRG ROM has zero size
+    EmptyPrgRom,
+    /// file ends in the middle of PRG ROM
+    TruncatedPrg {
+        /// file offset where PRG ROM starts
+        offset: usize,
+        /// PRG ROM size in header
+        expected: usize,
+        /// bytes available from the offset
+        actual: usize,
+    },
+    /// file ends in the middle of CHR ROM
+    TruncatedChr {
+        /// file offset where CHR ROM starts
+        offset: usize,
+        /// CHR ROM size in header
+        expected: usize,
+        /// bytes available from the offset
+        actual: usize,
+    },
+    /// mapper is not implemented
+    UnsupportedMapper {
+        /// mapper number
+        mapper: u16,
+        /// submapper number
+        submapper: u8,
+    },
+    /// NES 2.0 header has a value which can't be used
+    InvalidNes2Field {
+        /// header byte offset
+        offset: usize,
+        /// byte value
+        value: u8,
+        /// why the value is invalid
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::TruncatedHeader { expected, actual } => write!(
+                f,
+                "header needs {} bytes but file has only {} bytes",
+                expected, actual
+            ),
+            CartridgeError::BadMagic { found } => {
+                write!(f, "damaged header: bad magic {:02X?}", found)
+            }
+            CartridgeError::BadTrainer {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "trainer at {:#x} needs {} bytes but only {} bytes remain",
+                offset, expected, actual
+            ),
+            CartridgeError::EmptyPrgRom => write!(f, "PRG ROM size is zero"),
+            CartridgeError::TruncatedPrg {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "PRG ROM at {:#x} needs {} bytes but only {} bytes remain",
+                offset, expected, actual
+            ),
+            CartridgeError::TruncatedChr {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "CHR ROM at {:#x} needs {} bytes but only {} bytes remain",
+                offset, expected, actual
+            ),
+            CartridgeError::UnsupportedMapper { mapper, submapper } => {
+                write!(f, "unsupported mapper {} (submapper {})", mapper, submapper)
+            }
+            CartridgeError::InvalidNes2Field {
+                offset,
+                value,
+                reason,
+            } => write!(
+                f,
+                "invalid NES 2.0 header byte {} ({:#04x}): {}",
+                offset, value, reason
+            ),
+        }
+    }
+}
+
+impl Error for CartridgeError {}
@@ -0,0 +1,221 @@
+use super::CartridgeError;
+use crate::mapper::Mirroring;
+
+/// Size of iNES header
+pub const HEADER_LENGTH: usize = 16;
+/// Size of trainer placed between header and PRG ROM
+pub const TRAINER_LENGTH: usize = 512;
+
+const PRG_ROM_PAGE_UNIT: usize = 16 * 1024;
+const CHR_ROM_PAGE_UNIT: usize = 8 * 1024;
+const PRG_RAM_PAGE_UNIT: usize = 8 * 1024;
+
+/// Variant of the header format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderFormat {
+    /// iNES 1.0 (including archaic iNES)
+    INes,
+    /// NES 2.0
+    Nes2,
+}
+
+/// Video timing the ROM was made for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TvSystem {
+    /// RP2C02 (North America, Japan)
+    Ntsc,
+    /// RP2C07 (Europe, Australia)
+    Pal,
+    /// Works on both NTSC and PAL
+    MultiRegion,
+    /// UMC 6527P (Russia)
+    Dendy,
+}
+
+/// Console the ROM runs on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleType {
+    /// Nintendo Entertainment System / Family Computer
+    Nes,
+    /// Nintendo Vs. System with PPU type and hardware type
+    VsSystem {
+        /// Vs. PPU type (NES 2.0 byte 13 low nibble)
+        ppu_type: u8,
+        /// Vs. hardware type (NES 2.0 byte 13 high nibble)
+        hardware_type: u8,
+    },
+    /// Nintendo Playchoice 10
+    Playchoice10,
+    /// Extended console type (NES 2.0 byte 13 low nibble)
+    Extended(u8),
+}
+
+/// Parsed iNES / NES 2.0 header
+/// See https://wiki.nesdev.com/w/index.php/NES_2.0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CartridgeHeader {
+    /// header format
+    pub format: HeaderFormat,
+    /// PRG ROM size in bytes
+    pub prg_rom_size: usize,
+    /// CHR ROM size in bytes
+    pub chr_rom_size: usize,
+    /// mapper number
+    pub mapper: u16,
+    /// submapper number (always 0 for iNES 1.0)
+    pub submapper: u8,
+    /// hard-wired nametable mirroring
+    pub mirroring: Mirroring,
+    /// cartridge provides its own four-screen VRAM
+    pub four_screen: bool,
+    /// cartridge contains battery-backed memory
+    pub battery: bool,
+    /// 512-byte trainer is present before PRG ROM
+    pub trainer: bool,
+    /// volatile PRG RAM size in bytes
+    pub prg_ram_size: usize,
+    /// non-volatile PRG RAM (NVRAM / EEPROM) size in bytes
+    pub prg_nvram_size: usize,
+    /// volatile CHR RAM size in bytes
+    pub chr_ram_size: usize,
+    /// non-volatile CHR RAM size in bytes
+    pub chr_nvram_size: usize,
+    /// CPU/PPU timing
+    pub tv_system: TvSystem,
+    /// console type
+    pub console_type: ConsoleType,
+    /// default expansion device (NES 2.0 byte 15)
+    pub expansion_device: u8,
+}
+
+impl CartridgeHeader {
+    /// Parse 16 bytes header. The first 4 bytes must be already verified.
+    pub fn parse(d: &[u8; HEADER_LENGTH]) -> Result<CartridgeHeader, CartridgeError> {
+        let flags6 = d[6];
+        let flags7 = d[7];
+        let format = if flags7 & 0x0c == 0x08 {
+            HeaderFormat::Nes2
+        } else {
+            HeaderFormat::INes
+        };
+        let mirroring = if flags6 & 0x01 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        let four_screen = flags6 & 0x08 != 0;
+        let battery = flags6 & 0x02 != 0;
+        let trainer = flags6 & 0x04 != 0;
+
+        match format {
+            HeaderFormat::Nes2 => {
+                let prg_rom_size = nes2_rom_size(4, d[4], d[9] & 0x0f, PRG_ROM_PAGE_UNIT)?;
+                let chr_rom_size = nes2_rom_size(5, d[5], d[9] >> 4, CHR_ROM_PAGE_UNIT)?;
+                let console_type = match flags7 & 0x03 {
+                    0 => ConsoleType::Nes,
+                    1 => ConsoleType::VsSystem {
+                        ppu_type: d[13] & 0x0f,
+                        hardware_type: d[13] >> 4,
+                    },
+                    2 => ConsoleType::Playchoice10,
+                    _ => ConsoleType::Extended(d[13] & 0x0f),
+                };
+                let tv_system = match d[12] & 0x03 {
+                    0 => TvSystem::Ntsc,
+                    1 => TvSystem::Pal,
+                    2 => TvSystem::MultiRegion,
+                    _ => TvSystem::Dendy,
+                };
+                Ok(CartridgeHeader {
+                    format,
+                    prg_rom_size,
+                    chr_rom_size,
+                    mapper: u16::from(flags6 >> 4)
+                        | u16::from(flags7 & 0xf0)
+                        | (u16::from(d[8] & 0x0f) << 8),
+                    submapper: d[8] >> 4,
+                    mirroring,
+                    four_screen,
+                    battery,
+                    trainer,
+                    prg_ram_size: nes2_ram_size(d[10] & 0x0f),
+                    prg_nvram_size: nes2_ram_size(d[10] >> 4),
+                    chr_ram_size: nes2_ram_size(d[11] & 0x0f),
+                    chr_nvram_size: nes2_ram_size(d[11] >> 4),
+                    tv_system,
+                    console_type,
+                    expansion_device: d[15] & 0x3f,
+                })
+            }
+            HeaderFormat::INes => {
+                // Archaic dumpers wrote signatures like "DiskDude!" into bytes 7-15,
+                // in which case the upper nibble of the mapper number is garbage.
+                let archaic = d[12..16].iter().any(|&b| b != 0);
+                let mapper_high = if archaic { 0 } else { flags7 & 0xf0 };
+                let chr_rom_size = usize::from(d[5]) * CHR_ROM_PAGE_UNIT;
+                let prg_ram_pages = if archaic || d[8] == 0 { 1 } else { d[8] };
+                let console_type = match (archaic, flags7 & 0x03) {
+                    (false, 1) => ConsoleType::VsSystem {
+                        ppu_type: 0,
+                        hardware_type: 0,
+                    },
+                    (false, 2) => ConsoleType::Playchoice10,
+                    _ => ConsoleType::Nes,
+                };
+                let tv_system = if !archaic && d[9] & 0x01 != 0 {
+                    TvSystem::Pal
+                } else {
+                    TvSystem::Ntsc
+                };
+                Ok(CartridgeHeader {
+                    format,
+                    prg_rom_size: usize::from(d[4]) * PRG_ROM_PAGE_UNIT,
+                    chr_rom_size,
+                    mapper: u16::from(mapper_high | (flags6 >> 4)),
+                    submapper: 0,
+                    mirroring,
+                    four_screen,
+                    battery,
+                    trainer,
+                    prg_ram_size: usize::from(prg_ram_pages) * PRG_RAM_PAGE_UNIT,
+                    prg_nvram_size: 0,
+                    chr_ram_size: if chr_rom_size == 0 {
+                        CHR_ROM_PAGE_UNIT
+                    } else {
+                        0
+                    },
+                    chr_nvram_size: 0,
+                    tv_system,
+                    console_type,
+                    expansion_device: 0,
+                })
+            }
+        }
+    }
+}
+
+fn nes2_rom_size(offset: usize, lsb: u8, msb: u8, unit: usize) -> Result<usize, CartridgeError> {
+    if msb == 0x0f {
+        // exponent-multiplier notation: 2^E * (MM * 2 + 1)
+        let exponent = u32::from(lsb >> 2);
+        let multiplier = usize::from(lsb & 0x03) * 2 + 1;
+        1usize
+            .checked_shl(exponent)
+            .and_then(|size| size.checked_mul(multiplier))
+            .ok_or(CartridgeError::InvalidNes2Field {
+                offset,
+                value: lsb,
+                reason: "ROM size exponent is too large",
+            })
+    } else {
+        Ok(((usize::from(msb) << 8) | usize::from(lsb)) * unit)
+    }
+}
+
+fn nes2_ram_size(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::{self, Write};
+
+/// PRG byte was fetched as an opcode or operand
+pub const PRG_CODE: u8 = 0x01;
+/// PRG byte was loaded as data
+pub const PRG_DATA: u8 = 0x02;
+/// PRG byte is the destination of an indirect jump, `JMP ($nnnn)`
+pub const PRG_INDIRECT_CODE: u8 = 0x10;
+/// PRG byte was loaded through a pointer, `($nn,X)` or `($nn),Y`
+pub const PRG_INDIRECT_DATA: u8 = 0x20;
+/// CHR byte was fetched by the PPU to draw a pixel
+pub const CHR_RENDERED: u8 = 0x01;
+/// CHR byte was read by the cpu through $2007
+pub const CHR_READ: u8 = 0x02;
+
+// PRG flags also record which 8KB cpu window the byte was accessed through
+fn prg_window_bits(addr: u16) -> u8 {
+    ((addr >> 11) & 0x0c) as u8
+}
+
+/// Code/Data Log. One flag byte per PRG ROM and CHR ROM byte, in FCEUX `.cdl` layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeDataLog {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+}
+
+impl CodeDataLog {
+    /// Create empty log for ROM sizes in bytes
+    pub fn new(prg_rom_size: usize, chr_rom_size: usize) -> CodeDataLog {
+        CodeDataLog {
+            prg: vec![0; prg_rom_size],
+            chr: vec![0; chr_rom_size],
+        }
+    }
+
+    /// Set flags on the PRG ROM byte at offset, accessed through cpu address `addr`
+    pub fn log_prg(&mut self, offset: usize, addr: u16, flags: u8) {
+        if let Some(byte) = self.prg.get_mut(offset) {
+            *byte = (*byte & !0x0c) | flags | prg_window_bits(addr);
+        }
+    }
+
+    /// Set flags on the CHR ROM byte at offset
+    pub fn log_chr(&mut self, offset: usize, flags: u8) {
+        if let Some(byte) = self.chr.get_mut(offset) {
+            *byte |= flags;
+        }
+    }
+
+    /// Flags by PRG ROM offset
+    pub fn prg(&self) -> &[u8] {
+        &self.prg
+    }
+
+    /// Flags by CHR ROM offset
+    pub fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    /// Number of PRG ROM bytes with any of the flags
+    pub fn count_prg(&self, flags: u8) -> usize {
+        self.prg.iter().filter(|&&byte| byte & flags != 0).count()
+    }
+
+    /// Number of CHR ROM bytes with any of the flags
+    pub fn count_chr(&self, flags: u8) -> usize {
+        self.chr.iter().filter(|&&byte| byte & flags != 0).count()
+    }
+
+    /// Write an FCEUX `.cdl` file: PRG flags followed by CHR flags
+    pub fn write_fceux<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.prg)?;
+        writer.write_all(&self.chr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cartridge, Nes};
+
+    #[test]
+    fn prg_flags_record_the_last_cpu_window() {
+        let mut log = CodeDataLog::new(4, 0);
+        log.log_prg(0, 0x8000, PRG_CODE);
+        log.log_prg(1, 0xa000, PRG_CODE);
+        log.log_prg(2, 0xc000, PRG_DATA);
+        log.log_prg(3, 0xe000, PRG_DATA);
+        assert_eq!(log.prg(), &[0x01, 0x05, 0x0a, 0x0e]);
+        // flags accumulate and the window is replaced
+        log.log_prg(0, 0xe000, PRG_DATA);
+        assert_eq!(log.prg()[0], 0x0f);
+    }
+
+    #[test]
+    fn access_sequence() {
+        let mut prg = vec![0; 0x8000];
+        let code = [
+            0xa9, 0x00, // LDA #$00
+            0x85, 0x00, // STA $00
+            0xa9, 0xc0, // LDA #$C0
+            0x85, 0x01, // STA $01
+            0xa0, 0x05, // LDY #$05
+            0xb1, 0x00, // LDA ($00),Y
+            0xad, 0x00, 0xe0, // LDA $E000
+            0x6c, 0x00, 0xa0, // JMP ($A000)
+        ];
+        prg[..code.len()].copy_from_slice(&code);
+        // jump vector at $A000 and its target
+        prg[0x2000..0x2002].copy_from_slice(&[0x20, 0x80]);
+        prg[0x0020..0x0023].copy_from_slice(&[0x4c, 0x20, 0x80]); // JMP $8020
+        prg[0x7ffc..0x7ffe].copy_from_slice(&[0x00, 0x80]);
+        let mut nes = Nes::with_cartridge(Cartridge::nrom(&prg));
+        nes.start_code_data_log();
+        for _ in 0..9 {
+            nes.step();
+        }
+
+        let log = nes.take_code_data_log().unwrap();
+        let prg = log.prg();
+        assert!(prg[..code.len()].iter().all(|&flags| flags == PRG_CODE));
+        assert_eq!(prg[code.len()], 0);
+        // pointer read through $C000
+        assert_eq!(prg[0x4005], PRG_DATA | PRG_INDIRECT_DATA | 0x08);
+        assert_eq!(prg[0x4004], 0);
+        assert_eq!(prg[0x6000], PRG_DATA | 0x0c);
+        // jump vector through $A000
+        assert_eq!(&prg[0x2000..0x2002], &[PRG_DATA | 0x04, PRG_DATA | 0x04]);
+        assert_eq!(prg[0x0020], PRG_CODE | PRG_INDIRECT_CODE);
+        assert_eq!(&prg[0x0021..0x0023], &[PRG_CODE, PRG_CODE]);
+        assert_eq!(log.count_prg(PRG_CODE), code.len() + 3);
+
+        let mut file = Vec::new();
+        log.write_fceux(&mut file).unwrap();
+        assert_eq!(file.len(), 0x8000 + 0x2000);
+        assert_eq!(&file[..0x8000], prg);
+    }
+}
@@ -1,10 +1,14 @@
-mod instructions;
+pub(crate) mod instructions;
 mod register;
 
 use self::instructions::{AddressingMode, Instruction, Opcode};
 use self::register::Register;
 use crate::bus::Bus;
-use log::{debug, trace};
+use log::debug;
+
+// Constant ORed into A by the unstable XAA and LXA.
+// It differs between chips. $EE is the most common value.
+const UNSTABLE_MAGIC: u8 = 0xee;
 
 #[derive(Debug, Copy, Clone)]
 enum Operand {
@@ -14,10 +18,31 @@ enum Operand {
     Memory(u16),
 }
 
+/// Snapshot of cpu registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+    /// accumulator
+    pub a: u8,
+    /// index register X
+    pub x: u8,
+    /// index register Y
+    pub y: u8,
+    /// status register
+    pub p: u8,
+    /// stack pointer
+    pub s: u8,
+    /// program counter
+    pub pc: u16,
+}
+
 #[derive(Default, Clone)]
 /// Cpu model with register
 pub struct Cpu {
     reg: Register,
+    // interrupt disable flag seen by the interrupt poll of the last instruction
+    irq_disabled_at_poll: bool,
+    // set by KIL. only reset recovers
+    halted: bool,
 }
 
 impl Cpu {
@@ -25,41 +50,156 @@ impl Cpu {
     pub fn new() -> Self {
         Cpu {
             reg: Register::new(),
+            irq_disabled_at_poll: true,
+            halted: false,
         }
     }
 }
 
 impl Cpu {
     /// Fetches and executes instruction.
-    /// Returns the number of clocks
+    /// Every bus access takes 1 clock, and the bus runs PPU and APU on each of them.
+    /// Returns the number of clocks, not counting DMC stalls
     pub fn exec(&mut self, bus: &mut Bus) -> u8 {
+        if self.halted {
+            // address bus is stuck at $FFFF
+            bus.dummy_load(0xffff);
+            return 1;
+        }
+        let start = bus.cycles() - bus.stalled_cycles();
         let inst = self.fetch_instruction(bus);
-        let addr = self.fetch_operand(bus, inst.mode);
-        trace!("{:04X} {:?} {:?}", self.reg.PC, inst.opcode, addr);
+        let (addr, page_crossed) = self.fetch_operand(bus, inst);
         let inst_bytes = 1 + u16::from(inst.mode.operand_bytes());
+        let expected_cycles = inst.cycles + self.extra_cycles(inst.opcode, inst.mode, page_crossed);
 
         self.reg.PC += inst_bytes;
+        let irq_disabled = self.reg.P.interrupt_disable_flag();
+        // loads through ($nn,X) and ($nn),Y pointers are flagged in the Code/Data Log
+        bus.set_indirect_data(matches!(
+            inst.mode,
+            AddressingMode::IndirectX | AddressingMode::IndirectY
+        ));
         self.execute_instruction(bus, inst.opcode, addr);
+        bus.set_indirect_data(false);
+        // CLI, SEI and PLP change the flag after the interrupt is polled,
+        // so the new value takes effect one instruction later.
+        self.irq_disabled_at_poll = match inst.opcode {
+            Opcode::CLI | Opcode::SEI | Opcode::PLP => irq_disabled,
+            _ => self.reg.P.interrupt_disable_flag(),
+        };
+
+        let cycles = (bus.cycles() - bus.stalled_cycles() - start) as u8;
+        debug_assert_eq!(
+            cycles, expected_cycles,
+            "{:?} {:?} took wrong number of cycles",
+            inst.opcode, inst.mode
+        );
+        cycles
+    }
 
-        inst.cycles
+    // Cycles on top of the base count in the instruction table.
+    // Must be called before the instruction changes flags.
+    fn extra_cycles(&self, op: Opcode, mode: AddressingMode, page_crossed: bool) -> u8 {
+        match mode {
+            AddressingMode::Relative if self.branch_condition(op) => 1 + page_crossed as u8,
+            AddressingMode::Relative => 0,
+            _ => (page_crossed && op.has_page_cross_penalty()) as u8,
+        }
     }
 
-    /// Reset state
+    fn branch_condition(&self, op: Opcode) -> bool {
+        match op {
+            Opcode::BCC => !self.reg.P.carry_flag(),
+            Opcode::BCS => self.reg.P.carry_flag(),
+            Opcode::BEQ => self.reg.P.zero_flag(),
+            Opcode::BMI => self.reg.P.negative_flag(),
+            Opcode::BNE => !self.reg.P.zero_flag(),
+            Opcode::BPL => !self.reg.P.negative_flag(),
+            Opcode::BVC => !self.reg.P.overflow_flag(),
+            Opcode::BVS => self.reg.P.overflow_flag(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Current registers
+    pub fn registers(&self) -> Registers {
+        Registers {
+            a: self.reg.A,
+            x: self.reg.X,
+            y: self.reg.Y,
+            p: self.reg.P.to_u8(),
+            s: self.reg.S,
+            pc: self.reg.PC,
+        }
+    }
+
+    /// Jump to the address. e.g. nestest automation mode starts at $C000.
+    pub fn set_pc(&mut self, pc: u16) {
+        self.reg.PC = pc;
+    }
+
+    /// Reset state on power on
     pub fn reset(&mut self, bus: &mut Bus) {
         self.reg = Register::new();
+        // reset sequence moves the stack pointer from 0 to $FD
+        self.reg.S = 0;
+        self.soft_reset(bus);
+    }
+
+    /// Reset by the reset button. A, X and Y are kept.
+    pub fn soft_reset(&mut self, bus: &mut Bus) {
+        // 2 cycles to read opcode and 3 cycles of stack reads instead of pushes
+        bus.dummy_load(self.reg.PC);
+        bus.dummy_load(self.reg.PC);
+        for _ in 0..3 {
+            bus.dummy_load(u16::from(self.reg.S) + 0x100);
+            self.reg.S = self.reg.S.wrapping_sub(1);
+        }
+        self.reg.P.set_interrupt_disable_flag(true);
         self.reg.PC = bus.load_w(0xfffc);
+        self.halted = false;
+        self.irq_disabled_at_poll = true;
+    }
+
+    /// True after KIL instruction locked up the cpu
+    pub fn is_halted(&self) -> bool {
+        self.halted
     }
 
     /// NMI interrupts
     pub fn nmi(&mut self, bus: &mut Bus) {
-        // TODO: this is for testing. implement correctly
-        self.reg.P.set_break_command(false);
+        if self.halted {
+            return;
+        }
+        // 2 cycles to read the next opcode which is discarded
+        bus.dummy_load(self.reg.PC);
+        bus.dummy_load(self.reg.PC);
+        self.interrupt(bus, 0xfffa, false);
+        debug!("nmi loaded {}", self.reg.PC);
+    }
+
+    /// IRQ interrupts. The line is level-triggered, so call this after every instruction
+    /// while the line is asserted. Ignored while interrupt disable flag is set.
+    pub fn irq(&mut self, bus: &mut Bus) {
+        if self.halted || self.irq_disabled_at_poll {
+            return;
+        }
+        bus.dummy_load(self.reg.PC);
+        bus.dummy_load(self.reg.PC);
+        self.interrupt(bus, 0xfffe, false);
+        debug!("irq loaded {}", self.reg.PC);
+    }
+
+    // Push PC and P, then jump to the vector. B flag is set in the pushed P only for BRK.
+    fn interrupt(&mut self, bus: &mut Bus, vector: u16, brk: bool) {
         self.push_stack_w(bus, self.reg.PC);
-        self.push_stack(bus, self.reg.P.to_u8());
+        let mut p = self.reg.P.clone();
+        p.set_break_command(brk);
+        self.push_stack(bus, p.to_u8());
         self.reg.P.set_interrupt_disable_flag(true);
+        self.irq_disabled_at_poll = true;
 
-        self.reg.PC = bus.load_w(0xfffa);
-        debug!("nmi loaded {}", self.reg.PC);
+        self.reg.PC = bus.load_w(vector);
     }
 
     fn set_zero_and_negative_flags(&mut self, val: u8) {
@@ -76,6 +216,15 @@ impl Cpu {
         }
     }
 
+    // Read-modify-write instructions write back the unmodified value before the result
+    fn load_rmw_inst(&mut self, bus: &mut Bus, addr: Operand) -> u8 {
+        let val = self.load_inst(bus, addr);
+        if let Operand::Memory(addr) = addr {
+            bus.store(addr, val);
+        }
+        val
+    }
+
     fn write_inst(&mut self, bus: &mut Bus, addr: Operand, val: u8) {
         match addr {
             Operand::None => unreachable!(),
@@ -92,6 +241,19 @@ impl Cpu {
         }
     }
 
+    fn branch_inst(&mut self, bus: &mut Bus, addr: Operand) {
+        let target = match addr {
+            Operand::Memory(addr) => addr,
+            _ => unreachable!(),
+        };
+        // Taken branch reads the next opcode, and once more while fixing the high byte
+        bus.dummy_load(self.reg.PC);
+        if is_page_crossed(self.reg.PC, target) {
+            bus.dummy_load((self.reg.PC & 0xff00) | (target & 0x00ff));
+        }
+        self.jump(target);
+    }
+
     fn comp_inst(&mut self, x: u8, m: u8) {
         let r = x.wrapping_sub(m);
         self.reg.P.set_carry_flag(x >= m);
@@ -99,6 +261,83 @@ impl Cpu {
         self.reg.P.set_negative_flag((r & 0x80) != 0);
     }
 
+    fn adc_inst(&mut self, y: u8) {
+        let x = self.reg.A;
+        let (val, overflow1) = x.overflowing_add(y);
+        let (val, overflow2) = val.overflowing_add(self.reg.P.carry_flag() as u8);
+        let carry = overflow1 || overflow2;
+        let x_neg = (x & 0x80) != 0;
+        let y_neg = (y & 0x80) != 0;
+        let val_neg = (val & 0x80) != 0;
+        let overflow = (x_neg && y_neg && !val_neg) || (!x_neg && !y_neg && val_neg);
+        self.reg.A = val;
+        self.set_zero_and_negative_flags(val);
+        self.reg.P.set_carry_flag(carry);
+        self.reg.P.set_overflow_flag(overflow);
+    }
+
+    fn sbc_inst(&mut self, y: u8) {
+        let x = self.reg.A;
+        let (val, overflow1) = x.overflowing_sub(y);
+        let (val, overflow2) = val.overflowing_sub(1 - self.reg.P.carry_flag() as u8);
+        let carry = !(overflow1 || overflow2);
+        let x_neg = (x & 0x80) != 0;
+        let y_neg = (y & 0x80) != 0;
+        let val_neg = (val & 0x80) != 0;
+        let overflow = (x_neg && !y_neg && !val_neg) || (!x_neg && y_neg && val_neg);
+        self.reg.A = val;
+        self.set_zero_and_negative_flags(val);
+        self.reg.P.set_carry_flag(carry);
+        self.reg.P.set_overflow_flag(overflow);
+    }
+
+    fn asl_inst(&mut self, val: u8) -> u8 {
+        let new = (val & 0x7f) << 1;
+        self.reg.P.set_carry_flag(val & 0x80 != 0);
+        self.set_zero_and_negative_flags(new);
+        new
+    }
+
+    fn lsr_inst(&mut self, val: u8) -> u8 {
+        let new = val >> 1;
+        self.reg.P.set_carry_flag((val & 0x01) != 0);
+        self.set_zero_and_negative_flags(new);
+        new
+    }
+
+    fn rol_inst(&mut self, val: u8) -> u8 {
+        let c = self.reg.P.carry_flag() as u8;
+        let new = ((val & 0x7f) << 1) | c;
+        self.reg.P.set_carry_flag(val & 0x80 != 0);
+        self.set_zero_and_negative_flags(new);
+        new
+    }
+
+    fn ror_inst(&mut self, val: u8) -> u8 {
+        let c = self.reg.P.carry_flag() as u8;
+        let new = (val >> 1) | (c << 7);
+        self.reg.P.set_carry_flag(val & 0x01 != 0);
+        self.set_zero_and_negative_flags(new);
+        new
+    }
+
+    // SHA, SHX, SHY and TAS store the value ANDed with the high byte of the base address + 1.
+    // When indexing crosses a page, the value also replaces the high byte of the address.
+    fn store_and_high_inst(&mut self, bus: &mut Bus, addr: Operand, val: u8, index: u8) {
+        let addr = match addr {
+            Operand::Memory(addr) => addr,
+            _ => unreachable!(),
+        };
+        let base = addr.wrapping_sub(u16::from(index));
+        let val = val & ((base >> 8) as u8).wrapping_add(1);
+        let addr = if is_page_crossed(base, addr) {
+            (u16::from(val) << 8) | (addr & 0x00ff)
+        } else {
+            addr
+        };
+        bus.store(addr, val);
+    }
+
     fn jump(&mut self, addr: u16) {
         self.reg.PC = addr;
     }
@@ -113,6 +352,11 @@ impl Cpu {
         self.push_stack(bus, (val & 0xff) as u8);
     }
 
+    // Stack pointer is incremented in a cycle which reads the current top
+    fn load_stack_top(&mut self, bus: &mut Bus) {
+        bus.dummy_load(u16::from(self.reg.S) + 0x100);
+    }
+
     fn pop_stack(&mut self, bus: &mut Bus) -> u8 {
         self.reg.S = self.reg.S.wrapping_add(1);
         bus.load(u16::from(self.reg.S) + 0x100)
@@ -125,64 +369,87 @@ impl Cpu {
     }
 
     fn fetch_instruction(&self, bus: &mut Bus) -> Instruction {
-        let code = bus.load(self.reg.PC);
+        let code = bus.fetch(self.reg.PC);
         Instruction::from_code(code)
     }
 
-    fn fetch_operand(&self, bus: &mut Bus, mode: AddressingMode) -> Operand {
+    // Returns the operand and whether indexing or branching crossed a page boundary
+    fn fetch_operand(&self, bus: &mut Bus, inst: Instruction) -> (Operand, bool) {
         let addr = self.reg.PC + 1;
-        match mode {
-            AddressingMode::Implied => Operand::None,
-            AddressingMode::Accumulator => Operand::Accumulator,
+        // Only reads skip the dummy read when the page is not crossed
+        let always_dummy_read = !inst.opcode.has_page_cross_penalty();
+        match inst.mode {
+            AddressingMode::Implied => {
+                // the byte after opcode is read and discarded
+                bus.dummy_load(addr);
+                (Operand::None, false)
+            }
+            AddressingMode::Accumulator => {
+                bus.dummy_load(addr);
+                (Operand::Accumulator, false)
+            }
             AddressingMode::Immediate => {
-                let value = bus.load(addr);
-                Operand::Immediate(value)
+                let value = bus.fetch(addr);
+                (Operand::Immediate(value), false)
             }
             AddressingMode::ZeroPage => {
-                let value = bus.load(addr);
-                Operand::Memory(u16::from(value))
+                let value = bus.fetch(addr);
+                (Operand::Memory(u16::from(value)), false)
             }
             AddressingMode::ZeroPageX => {
-                let value = bus.load(addr);
-                Operand::Memory(u16::from(value.wrapping_add(self.reg.X)))
+                let value = bus.fetch(addr);
+                bus.dummy_load(u16::from(value));
+                (
+                    Operand::Memory(u16::from(value.wrapping_add(self.reg.X))),
+                    false,
+                )
             }
             AddressingMode::ZeroPageY => {
-                let value = bus.load(addr);
-                Operand::Memory(u16::from(value.wrapping_add(self.reg.Y)))
+                let value = bus.fetch(addr);
+                bus.dummy_load(u16::from(value));
+                (
+                    Operand::Memory(u16::from(value.wrapping_add(self.reg.Y))),
+                    false,
+                )
             }
             AddressingMode::Absolute => {
-                let value = bus.load_w(addr);
-                Operand::Memory(value)
+                let value = bus.fetch_w(addr);
+                (Operand::Memory(value), false)
             }
             AddressingMode::AbsoluteX => {
-                let value = bus.load_w(addr);
-                Operand::Memory(value.wrapping_add(u16::from(self.reg.X)))
+                let base = bus.fetch_w(addr);
+                load_indexed(bus, base, self.reg.X, always_dummy_read)
             }
             AddressingMode::AbsoluteY => {
-                let value = bus.load_w(addr);
-                Operand::Memory(value.wrapping_add(u16::from(self.reg.Y)))
+                let base = bus.fetch_w(addr);
+                load_indexed(bus, base, self.reg.Y, always_dummy_read)
             }
             AddressingMode::Indirect => {
-                let addr = bus.load_w(addr);
-                let value = bus.load_w(addr);
-                Operand::Memory(value)
+                let addr = bus.fetch_w(addr);
+                let value = load_w_in_page(bus, addr);
+                bus.log_indirect_jump(value);
+                (Operand::Memory(value), false)
             }
             AddressingMode::IndirectX => {
-                let addr = bus.load(addr).wrapping_add(self.reg.X);
-                let value = bus.load_w(u16::from(addr));
-                Operand::Memory(value)
+                let addr = bus.fetch(addr);
+                bus.dummy_load(u16::from(addr));
+                let addr = addr.wrapping_add(self.reg.X);
+                let value = load_w_in_page(bus, u16::from(addr));
+                (Operand::Memory(value), false)
             }
             AddressingMode::IndirectY => {
-                let addr = bus.load(addr);
-                let result = bus
-                    .load_w(u16::from(addr))
-                    .wrapping_add(u16::from(self.reg.Y));
-                Operand::Memory(result)
+                let addr = bus.fetch(addr);
+                let base = load_w_in_page(bus, u16::from(addr));
+                load_indexed(bus, base, self.reg.Y, always_dummy_read)
             }
             AddressingMode::Relative => {
-                let value = bus.load(addr);
-                let next = i32::from(self.reg.PC) + i32::from(value as i8) + 2;
-                Operand::Memory(next as u16)
+                let value = bus.fetch(addr);
+                let base = self.reg.PC.wrapping_add(2);
+                let next = i32::from(base) + i32::from(value as i8);
+                (
+                    Operand::Memory(next as u16),
+                    is_page_crossed(base, next as u16),
+                )
             }
         }
     }
@@ -256,11 +523,13 @@ impl Cpu {
                 self.push_stack(bus, val);
             }
             Opcode::PLA => {
+                self.load_stack_top(bus);
                 let val = self.pop_stack(bus);
                 self.reg.A = val;
                 self.set_zero_and_negative_flags(val);
             }
             Opcode::PLP => {
+                self.load_stack_top(bus);
                 let val = self.pop_stack(bus);
                 self.reg.P.set_u8(val);
                 self.reg.P.set_break_command(false);
@@ -288,32 +557,12 @@ impl Cpu {
                 self.reg.P.set_negative_flag((m & 0x80) != 0);
             }
             Opcode::ADC => {
-                let (x, y) = (self.reg.A, self.load_inst(bus, addr));
-                let (val, overflow1) = x.overflowing_add(y);
-                let (val, overflow2) = val.overflowing_add(self.reg.P.carry_flag() as u8);
-                let carry = overflow1 || overflow2;
-                let x_neg = (x & 0x80) != 0;
-                let y_neg = (y & 0x80) != 0;
-                let val_neg = (val & 0x80) != 0;
-                let overflow = (x_neg && y_neg && !val_neg) || (!x_neg && !y_neg && val_neg);
-                self.reg.A = val;
-                self.set_zero_and_negative_flags(val);
-                self.reg.P.set_carry_flag(carry);
-                self.reg.P.set_overflow_flag(overflow);
+                let m = self.load_inst(bus, addr);
+                self.adc_inst(m);
             }
             Opcode::SBC => {
-                let (x, y) = (self.reg.A, self.load_inst(bus, addr));
-                let (val, overflow1) = x.overflowing_sub(y);
-                let (val, overflow2) = val.overflowing_sub(1 - self.reg.P.carry_flag() as u8);
-                let carry = !(overflow1 || overflow2);
-                let x_neg = (x & 0x80) != 0;
-                let y_neg = (y & 0x80) != 0;
-                let val_neg = (val & 0x80) != 0;
-                let overflow = (x_neg && !y_neg && !val_neg) || (!x_neg && y_neg && val_neg);
-                self.reg.A = val;
-                self.set_zero_and_negative_flags(val);
-                self.reg.P.set_carry_flag(carry);
-                self.reg.P.set_overflow_flag(overflow);
+                let m = self.load_inst(bus, addr);
+                self.sbc_inst(m);
             }
             Opcode::CMP => {
                 let a = self.reg.A;
@@ -331,7 +580,7 @@ impl Cpu {
                 self.comp_inst(y, m);
             }
             Opcode::INC => {
-                let val = self.load_inst(bus, addr).wrapping_add(1);
+                let val = self.load_rmw_inst(bus, addr).wrapping_add(1);
                 self.write_inst(bus, addr, val);
                 self.set_zero_and_negative_flags(val);
             }
@@ -346,7 +595,7 @@ impl Cpu {
                 self.set_zero_and_negative_flags(val);
             }
             Opcode::DEC => {
-                let val = self.load_inst(bus, addr).wrapping_sub(1);
+                let val = self.load_rmw_inst(bus, addr).wrapping_sub(1);
                 self.write_inst(bus, addr, val);
                 self.set_zero_and_negative_flags(val);
             }
@@ -361,33 +610,23 @@ impl Cpu {
                 self.set_zero_and_negative_flags(val);
             }
             Opcode::ASL => {
-                let val = self.load_inst(bus, addr);
-                let new = (val & 0x7f) << 1;
-                self.reg.P.set_carry_flag(val & 0x80 != 0);
-                self.set_zero_and_negative_flags(new);
+                let val = self.load_rmw_inst(bus, addr);
+                let new = self.asl_inst(val);
                 self.write_inst(bus, addr, new);
             }
             Opcode::LSR => {
-                let val = self.load_inst(bus, addr);
-                let new = val >> 1;
-                self.reg.P.set_carry_flag((val & 0x01) != 0);
-                self.set_zero_and_negative_flags(new);
+                let val = self.load_rmw_inst(bus, addr);
+                let new = self.lsr_inst(val);
                 self.write_inst(bus, addr, new);
             }
             Opcode::ROL => {
-                let val = self.load_inst(bus, addr);
-                let c = self.reg.P.carry_flag() as u8;
-                let new = ((val & 0x7f) << 1) | c;
-                self.reg.P.set_carry_flag(val & 0x80 != 0);
-                self.set_zero_and_negative_flags(new);
+                let val = self.load_rmw_inst(bus, addr);
+                let new = self.rol_inst(val);
                 self.write_inst(bus, addr, new);
             }
             Opcode::ROR => {
-                let val = self.load_inst(bus, addr);
-                let c = self.reg.P.carry_flag() as u8;
-                let new = (val >> 1) | (c << 7);
-                self.reg.P.set_carry_flag(val & 0x01 != 0);
-                self.set_zero_and_negative_flags(new);
+                let val = self.load_rmw_inst(bus, addr);
+                let new = self.ror_inst(val);
                 self.write_inst(bus, addr, new);
             }
             Opcode::JMP => {
@@ -395,51 +634,27 @@ impl Cpu {
             }
             Opcode::JSR => {
                 let pc = self.reg.PC - 1;
+                self.load_stack_top(bus);
                 self.push_stack_w(bus, pc);
                 self.jump_inst(addr);
             }
             Opcode::RTS => {
+                self.load_stack_top(bus);
                 let addr = self.pop_stack_w(bus);
+                // PC is incremented in its own cycle
+                bus.dummy_load(addr);
                 self.jump(addr + 1);
             }
-            Opcode::BCC => {
-                if !self.reg.P.carry_flag() {
-                    self.jump_inst(addr);
-                }
-            }
-            Opcode::BCS => {
-                if self.reg.P.carry_flag() {
-                    self.jump_inst(addr);
-                }
-            }
-            Opcode::BEQ => {
-                if self.reg.P.zero_flag() {
-                    self.jump_inst(addr);
-                }
-            }
-            Opcode::BMI => {
-                if self.reg.P.negative_flag() {
-                    self.jump_inst(addr);
-                }
-            }
-            Opcode::BNE => {
-                if !self.reg.P.zero_flag() {
-                    self.jump_inst(addr);
-                }
-            }
-            Opcode::BPL => {
-                if !self.reg.P.negative_flag() {
-                    self.jump_inst(addr);
-                }
-            }
-            Opcode::BVC => {
-                if !self.reg.P.overflow_flag() {
-                    self.jump_inst(addr);
-                }
-            }
-            Opcode::BVS => {
-                if self.reg.P.overflow_flag() {
-                    self.jump_inst(addr);
+            Opcode::BCC
+            | Opcode::BCS
+            | Opcode::BEQ
+            | Opcode::BMI
+            | Opcode::BNE
+            | Opcode::BPL
+            | Opcode::BVC
+            | Opcode::BVS => {
+                if self.branch_condition(op) {
+                    self.branch_inst(bus, addr);
                 }
             }
             Opcode::CLC => {
@@ -463,16 +678,166 @@ impl Cpu {
             Opcode::SEI => {
                 self.reg.P.set_interrupt_disable_flag(true);
             }
-            Opcode::BRK => unimplemented!(),
+            Opcode::BRK => {
+                // BRK is followed by a padding byte which is skipped on return
+                self.reg.PC += 1;
+                self.interrupt(bus, 0xfffe, true);
+            }
             Opcode::NOP => {
-                // no operation
+                // no operation, but multi-byte variants still read the operand
+                if let Operand::Memory(_) = addr {
+                    self.load_inst(bus, addr);
+                }
             }
             Opcode::RTI => {
+                self.load_stack_top(bus);
                 let status = self.pop_stack(bus);
                 let pc = self.pop_stack_w(bus);
                 self.reg.P.set_u8(status);
+                self.reg.P.set_break_command(false);
                 self.reg.PC = pc;
             }
+            Opcode::ALR => {
+                let val = self.reg.A & self.load_inst(bus, addr);
+                self.reg.A = self.lsr_inst(val);
+            }
+            Opcode::ANC => {
+                let val = self.reg.A & self.load_inst(bus, addr);
+                self.reg.A = val;
+                self.set_zero_and_negative_flags(val);
+                self.reg.P.set_carry_flag((val & 0x80) != 0);
+            }
+            Opcode::ARR => {
+                let val = self.reg.A & self.load_inst(bus, addr);
+                let c = self.reg.P.carry_flag() as u8;
+                let new = (val >> 1) | (c << 7);
+                self.reg.A = new;
+                self.set_zero_and_negative_flags(new);
+                self.reg.P.set_carry_flag((new & 0x40) != 0);
+                self.reg
+                    .P
+                    .set_overflow_flag(((new >> 6) ^ (new >> 5)) & 0x01 != 0);
+            }
+            Opcode::AXS => {
+                let x = self.reg.A & self.reg.X;
+                let m = self.load_inst(bus, addr);
+                self.comp_inst(x, m);
+                self.reg.X = x.wrapping_sub(m);
+            }
+            Opcode::DCP => {
+                let val = self.load_rmw_inst(bus, addr).wrapping_sub(1);
+                self.write_inst(bus, addr, val);
+                let a = self.reg.A;
+                self.comp_inst(a, val);
+            }
+            Opcode::ISC => {
+                let val = self.load_rmw_inst(bus, addr).wrapping_add(1);
+                self.write_inst(bus, addr, val);
+                self.sbc_inst(val);
+            }
+            Opcode::KIL => {
+                // the cpu locks up until reset
+                debug!("halted by {:?}", op);
+                self.halted = true;
+            }
+            Opcode::LAS => {
+                let val = self.load_inst(bus, addr) & self.reg.S;
+                self.reg.A = val;
+                self.reg.X = val;
+                self.reg.S = val;
+                self.set_zero_and_negative_flags(val);
+            }
+            Opcode::LAX => {
+                let val = self.load_inst(bus, addr);
+                self.reg.A = val;
+                self.reg.X = val;
+                self.set_zero_and_negative_flags(val);
+            }
+            Opcode::LXA => {
+                // unstable. uses the magic constant most consoles show
+                let val = (self.reg.A | UNSTABLE_MAGIC) & self.load_inst(bus, addr);
+                self.reg.A = val;
+                self.reg.X = val;
+                self.set_zero_and_negative_flags(val);
+            }
+            Opcode::RLA => {
+                let val = self.load_rmw_inst(bus, addr);
+                let new = self.rol_inst(val);
+                self.write_inst(bus, addr, new);
+                let a = self.reg.A & new;
+                self.reg.A = a;
+                self.set_zero_and_negative_flags(a);
+            }
+            Opcode::RRA => {
+                let val = self.load_rmw_inst(bus, addr);
+                let new = self.ror_inst(val);
+                self.write_inst(bus, addr, new);
+                self.adc_inst(new);
+            }
+            Opcode::SAX => {
+                let val = self.reg.A & self.reg.X;
+                self.write_inst(bus, addr, val);
+            }
+            Opcode::SHA => {
+                let (val, y) = (self.reg.A & self.reg.X, self.reg.Y);
+                self.store_and_high_inst(bus, addr, val, y);
+            }
+            Opcode::SHX => {
+                let (val, y) = (self.reg.X, self.reg.Y);
+                self.store_and_high_inst(bus, addr, val, y);
+            }
+            Opcode::SHY => {
+                let (val, x) = (self.reg.Y, self.reg.X);
+                self.store_and_high_inst(bus, addr, val, x);
+            }
+            Opcode::SLO => {
+                let val = self.load_rmw_inst(bus, addr);
+                let new = self.asl_inst(val);
+                self.write_inst(bus, addr, new);
+                let a = self.reg.A | new;
+                self.reg.A = a;
+                self.set_zero_and_negative_flags(a);
+            }
+            Opcode::SRE => {
+                let val = self.load_rmw_inst(bus, addr);
+                let new = self.lsr_inst(val);
+                self.write_inst(bus, addr, new);
+                let a = self.reg.A ^ new;
+                self.reg.A = a;
+                self.set_zero_and_negative_flags(a);
+            }
+            Opcode::TAS => {
+                self.reg.S = self.reg.A & self.reg.X;
+                let (val, y) = (self.reg.S, self.reg.Y);
+                self.store_and_high_inst(bus, addr, val, y);
+            }
+            Opcode::XAA => {
+                // unstable. uses the magic constant most consoles show
+                let val = (self.reg.A | UNSTABLE_MAGIC) & self.reg.X & self.load_inst(bus, addr);
+                self.reg.A = val;
+                self.set_zero_and_negative_flags(val);
+            }
         }
     }
 }
+
+// Indexing reads the address before the carry into the high byte is fixed.
+// Reads skip it if no carry happens.
+fn load_indexed(bus: &mut Bus, base: u16, index: u8, always_dummy_read: bool) -> (Operand, bool) {
+    let value = base.wrapping_add(u16::from(index));
+    let page_crossed = is_page_crossed(base, value);
+    if page_crossed || always_dummy_read {
+        bus.dummy_load((base & 0xff00) | (value & 0x00ff));
+    }
+    (Operand::Memory(value), page_crossed)
+}
+
+// Pointers in zero page and JMP ($xxFF) don't carry into the high byte
+fn load_w_in_page(bus: &mut Bus, addr: u16) -> u16 {
+    let next = (addr & 0xff00) | (addr.wrapping_add(1) & 0x00ff);
+    u16::from(bus.load(addr)) | (u16::from(bus.load(next)) << 8)
+}
+
+fn is_page_crossed(a: u16, b: u16) -> bool {
+    (a & 0xff00) != (b & 0xff00)
+}
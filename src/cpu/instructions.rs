@@ -109,6 +109,49 @@ pub enum Opcode {
     TXA,
     TXS,
     TYA,
+    // unofficial opcodes
+    ALR,
+    ANC,
+    ARR,
+    AXS,
+    DCP,
+    ISC,
+    KIL,
+    LAS,
+    LAX,
+    LXA,
+    RLA,
+    RRA,
+    SAX,
+    SHA,
+    SHX,
+    SHY,
+    SLO,
+    SRE,
+    TAS,
+    XAA,
+}
+
+impl Opcode {
+    // Indexed reads take 1 more cycle when the effective address crosses a page.
+    // Stores and read-modify-write instructions always spend that cycle.
+    pub fn has_page_cross_penalty(self) -> bool {
+        matches!(
+            self,
+            Opcode::ADC
+                | Opcode::AND
+                | Opcode::CMP
+                | Opcode::EOR
+                | Opcode::LAS
+                | Opcode::LAX
+                | Opcode::LDA
+                | Opcode::LDX
+                | Opcode::LDY
+                | Opcode::NOP
+                | Opcode::ORA
+                | Opcode::SBC
+        )
+    }
 }
 
 fn lookup_instruction(code: u8) -> Instruction {
@@ -125,6 +168,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x02 => Instruction {
+            opcode: Opcode::KIL,
+            mode: AddressingMode::Implied,
+            cycles: 2,
+            illegal: true,
+        },
+        0x03 => Instruction {
+            opcode: Opcode::SLO,
+            mode: AddressingMode::IndirectX,
+            cycles: 8,
+            illegal: true,
+        },
         0x04 => Instruction {
             opcode: Opcode::NOP,
             mode: AddressingMode::ZeroPage,
@@ -143,6 +198,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0x07 => Instruction {
+            opcode: Opcode::SLO,
+            mode: AddressingMode::ZeroPage,
+            cycles: 5,
+            illegal: true,
+        },
         0x08 => Instruction {
             opcode: Opcode::PHP,
             mode: AddressingMode::Implied,
@@ -161,6 +222,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: false,
         },
+        0x0b => Instruction {
+            opcode: Opcode::ANC,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
         0x0c => Instruction {
             opcode: Opcode::NOP,
             mode: AddressingMode::Absolute,
@@ -179,6 +246,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x0f => Instruction {
+            opcode: Opcode::SLO,
+            mode: AddressingMode::Absolute,
+            cycles: 6,
+            illegal: true,
+        },
         0x10 => Instruction {
             opcode: Opcode::BPL,
             mode: AddressingMode::Relative,
@@ -191,6 +264,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0x12 => Instruction {
+            opcode: Opcode::KIL,
+            mode: AddressingMode::Implied,
+            cycles: 2,
+            illegal: true,
+        },
+        0x13 => Instruction {
+            opcode: Opcode::SLO,
+            mode: AddressingMode::IndirectY,
+            cycles: 8,
+            illegal: true,
+        },
         0x14 => Instruction {
             opcode: Opcode::NOP,
             mode: AddressingMode::ZeroPageX,
@@ -209,6 +294,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x17 => Instruction {
+            opcode: Opcode::SLO,
+            mode: AddressingMode::ZeroPageX,
+            cycles: 6,
+            illegal: true,
+        },
         0x18 => Instruction {
             opcode: Opcode::CLC,
             mode: AddressingMode::Implied,
@@ -227,6 +318,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: true,
         },
+        0x1b => Instruction {
+            opcode: Opcode::SLO,
+            mode: AddressingMode::AbsoluteY,
+            cycles: 7,
+            illegal: true,
+        },
+        0x1c => Instruction {
+            opcode: Opcode::NOP,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 4,
+            illegal: true,
+        },
         0x1d => Instruction {
             opcode: Opcode::ORA,
             mode: AddressingMode::AbsoluteX,
@@ -239,6 +342,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 7,
             illegal: false,
         },
+        0x1f => Instruction {
+            opcode: Opcode::SLO,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 7,
+            illegal: true,
+        },
         0x20 => Instruction {
             opcode: Opcode::JSR,
             mode: AddressingMode::Absolute,
@@ -251,6 +360,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x22 => Instruction {
+            opcode: Opcode::KIL,
+            mode: AddressingMode::Implied,
+            cycles: 2,
+            illegal: true,
+        },
+        0x23 => Instruction {
+            opcode: Opcode::RLA,
+            mode: AddressingMode::IndirectX,
+            cycles: 8,
+            illegal: true,
+        },
         0x24 => Instruction {
             opcode: Opcode::BIT,
             mode: AddressingMode::ZeroPage,
@@ -269,6 +390,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0x27 => Instruction {
+            opcode: Opcode::RLA,
+            mode: AddressingMode::ZeroPage,
+            cycles: 5,
+            illegal: true,
+        },
         0x28 => Instruction {
             opcode: Opcode::PLP,
             mode: AddressingMode::Implied,
@@ -287,6 +414,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: false,
         },
+        0x2b => Instruction {
+            opcode: Opcode::ANC,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
         0x2c => Instruction {
             opcode: Opcode::BIT,
             mode: AddressingMode::Absolute,
@@ -305,6 +438,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x2f => Instruction {
+            opcode: Opcode::RLA,
+            mode: AddressingMode::Absolute,
+            cycles: 6,
+            illegal: true,
+        },
         0x30 => Instruction {
             opcode: Opcode::BMI,
             mode: AddressingMode::Relative,
@@ -317,6 +456,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0x32 => Instruction {
+            opcode: Opcode::KIL,
+            mode: AddressingMode::Implied,
+            cycles: 2,
+            illegal: true,
+        },
+        0x33 => Instruction {
+            opcode: Opcode::RLA,
+            mode: AddressingMode::IndirectY,
+            cycles: 8,
+            illegal: true,
+        },
         0x34 => Instruction {
             opcode: Opcode::NOP,
             mode: AddressingMode::ZeroPageX,
@@ -335,6 +486,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x37 => Instruction {
+            opcode: Opcode::RLA,
+            mode: AddressingMode::ZeroPageX,
+            cycles: 6,
+            illegal: true,
+        },
         0x38 => Instruction {
             opcode: Opcode::SEC,
             mode: AddressingMode::Implied,
@@ -353,6 +510,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: true,
         },
+        0x3b => Instruction {
+            opcode: Opcode::RLA,
+            mode: AddressingMode::AbsoluteY,
+            cycles: 7,
+            illegal: true,
+        },
+        0x3c => Instruction {
+            opcode: Opcode::NOP,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 4,
+            illegal: true,
+        },
         0x3d => Instruction {
             opcode: Opcode::AND,
             mode: AddressingMode::AbsoluteX,
@@ -365,6 +534,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 7,
             illegal: false,
         },
+        0x3f => Instruction {
+            opcode: Opcode::RLA,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 7,
+            illegal: true,
+        },
         0x40 => Instruction {
             opcode: Opcode::RTI,
             mode: AddressingMode::Implied,
@@ -377,6 +552,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x42 => Instruction {
+            opcode: Opcode::KIL,
+            mode: AddressingMode::Implied,
+            cycles: 2,
+            illegal: true,
+        },
+        0x43 => Instruction {
+            opcode: Opcode::SRE,
+            mode: AddressingMode::IndirectX,
+            cycles: 8,
+            illegal: true,
+        },
         0x44 => Instruction {
             opcode: Opcode::NOP,
             mode: AddressingMode::ZeroPage,
@@ -395,6 +582,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0x47 => Instruction {
+            opcode: Opcode::SRE,
+            mode: AddressingMode::ZeroPage,
+            cycles: 5,
+            illegal: true,
+        },
         0x48 => Instruction {
             opcode: Opcode::PHA,
             mode: AddressingMode::Implied,
@@ -413,6 +606,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: false,
         },
+        0x4b => Instruction {
+            opcode: Opcode::ALR,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
         0x4c => Instruction {
             opcode: Opcode::JMP,
             mode: AddressingMode::Absolute,
@@ -431,6 +630,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x4f => Instruction {
+            opcode: Opcode::SRE,
+            mode: AddressingMode::Absolute,
+            cycles: 6,
+            illegal: true,
+        },
         0x50 => Instruction {
             opcode: Opcode::BVC,
             mode: AddressingMode::Relative,
@@ -443,6 +648,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0x52 => Instruction {
+            opcode: Opcode::KIL,
+            mode: AddressingMode::Implied,
+            cycles: 2,
+            illegal: true,
+        },
+        0x53 => Instruction {
+            opcode: Opcode::SRE,
+            mode: AddressingMode::IndirectY,
+            cycles: 8,
+            illegal: true,
+        },
         0x54 => Instruction {
             opcode: Opcode::NOP,
             mode: AddressingMode::ZeroPageX,
@@ -461,6 +678,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x57 => Instruction {
+            opcode: Opcode::SRE,
+            mode: AddressingMode::ZeroPageX,
+            cycles: 6,
+            illegal: true,
+        },
         0x58 => Instruction {
             opcode: Opcode::CLI,
             mode: AddressingMode::Implied,
@@ -479,6 +702,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: true,
         },
+        0x5b => Instruction {
+            opcode: Opcode::SRE,
+            mode: AddressingMode::AbsoluteY,
+            cycles: 7,
+            illegal: true,
+        },
+        0x5c => Instruction {
+            opcode: Opcode::NOP,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 4,
+            illegal: true,
+        },
         0x5d => Instruction {
             opcode: Opcode::EOR,
             mode: AddressingMode::AbsoluteX,
@@ -491,6 +726,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 7,
             illegal: false,
         },
+        0x5f => Instruction {
+            opcode: Opcode::SRE,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 7,
+            illegal: true,
+        },
         0x60 => Instruction {
             opcode: Opcode::RTS,
             mode: AddressingMode::Implied,
@@ -503,6 +744,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x62 => Instruction {
+            opcode: Opcode::KIL,
+            mode: AddressingMode::Implied,
+            cycles: 2,
+            illegal: true,
+        },
+        0x63 => Instruction {
+            opcode: Opcode::RRA,
+            mode: AddressingMode::IndirectX,
+            cycles: 8,
+            illegal: true,
+        },
         0x64 => Instruction {
             opcode: Opcode::NOP,
             mode: AddressingMode::ZeroPage,
@@ -521,6 +774,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0x67 => Instruction {
+            opcode: Opcode::RRA,
+            mode: AddressingMode::ZeroPage,
+            cycles: 5,
+            illegal: true,
+        },
         0x68 => Instruction {
             opcode: Opcode::PLA,
             mode: AddressingMode::Implied,
@@ -539,6 +798,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: false,
         },
+        0x6b => Instruction {
+            opcode: Opcode::ARR,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
         0x6c => Instruction {
             opcode: Opcode::JMP,
             mode: AddressingMode::Indirect,
@@ -557,6 +822,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x6f => Instruction {
+            opcode: Opcode::RRA,
+            mode: AddressingMode::Absolute,
+            cycles: 6,
+            illegal: true,
+        },
         0x70 => Instruction {
             opcode: Opcode::BVS,
             mode: AddressingMode::Relative,
@@ -569,6 +840,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0x72 => Instruction {
+            opcode: Opcode::KIL,
+            mode: AddressingMode::Implied,
+            cycles: 2,
+            illegal: true,
+        },
+        0x73 => Instruction {
+            opcode: Opcode::RRA,
+            mode: AddressingMode::IndirectY,
+            cycles: 8,
+            illegal: true,
+        },
         0x74 => Instruction {
             opcode: Opcode::NOP,
             mode: AddressingMode::ZeroPageX,
@@ -587,6 +870,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x77 => Instruction {
+            opcode: Opcode::RRA,
+            mode: AddressingMode::ZeroPageX,
+            cycles: 6,
+            illegal: true,
+        },
         0x78 => Instruction {
             opcode: Opcode::SEI,
             mode: AddressingMode::Implied,
@@ -605,6 +894,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: true,
         },
+        0x7b => Instruction {
+            opcode: Opcode::RRA,
+            mode: AddressingMode::AbsoluteY,
+            cycles: 7,
+            illegal: true,
+        },
+        0x7c => Instruction {
+            opcode: Opcode::NOP,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 4,
+            illegal: true,
+        },
         0x7d => Instruction {
             opcode: Opcode::ADC,
             mode: AddressingMode::AbsoluteX,
@@ -617,12 +918,36 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 7,
             illegal: false,
         },
+        0x7f => Instruction {
+            opcode: Opcode::RRA,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 7,
+            illegal: true,
+        },
+        0x80 => Instruction {
+            opcode: Opcode::NOP,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
         0x81 => Instruction {
             opcode: Opcode::STA,
             mode: AddressingMode::IndirectX,
             cycles: 6,
             illegal: false,
         },
+        0x82 => Instruction {
+            opcode: Opcode::NOP,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
+        0x83 => Instruction {
+            opcode: Opcode::SAX,
+            mode: AddressingMode::IndirectX,
+            cycles: 6,
+            illegal: true,
+        },
         0x84 => Instruction {
             opcode: Opcode::STY,
             mode: AddressingMode::ZeroPage,
@@ -641,18 +966,36 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 3,
             illegal: false,
         },
+        0x87 => Instruction {
+            opcode: Opcode::SAX,
+            mode: AddressingMode::ZeroPage,
+            cycles: 3,
+            illegal: true,
+        },
         0x88 => Instruction {
             opcode: Opcode::DEY,
             mode: AddressingMode::Implied,
             cycles: 2,
             illegal: false,
         },
+        0x89 => Instruction {
+            opcode: Opcode::NOP,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
         0x8a => Instruction {
             opcode: Opcode::TXA,
             mode: AddressingMode::Implied,
             cycles: 2,
             illegal: false,
         },
+        0x8b => Instruction {
+            opcode: Opcode::XAA,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
         0x8c => Instruction {
             opcode: Opcode::STY,
             mode: AddressingMode::Absolute,
@@ -671,6 +1014,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 4,
             illegal: false,
         },
+        0x8f => Instruction {
+            opcode: Opcode::SAX,
+            mode: AddressingMode::Absolute,
+            cycles: 4,
+            illegal: true,
+        },
         0x90 => Instruction {
             opcode: Opcode::BCC,
             mode: AddressingMode::Relative,
@@ -683,6 +1032,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0x92 => Instruction {
+            opcode: Opcode::KIL,
+            mode: AddressingMode::Implied,
+            cycles: 2,
+            illegal: true,
+        },
+        0x93 => Instruction {
+            opcode: Opcode::SHA,
+            mode: AddressingMode::IndirectY,
+            cycles: 6,
+            illegal: true,
+        },
         0x94 => Instruction {
             opcode: Opcode::STY,
             mode: AddressingMode::ZeroPageX,
@@ -701,6 +1062,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 4,
             illegal: false,
         },
+        0x97 => Instruction {
+            opcode: Opcode::SAX,
+            mode: AddressingMode::ZeroPageY,
+            cycles: 4,
+            illegal: true,
+        },
         0x98 => Instruction {
             opcode: Opcode::TYA,
             mode: AddressingMode::Implied,
@@ -719,12 +1086,36 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: false,
         },
+        0x9b => Instruction {
+            opcode: Opcode::TAS,
+            mode: AddressingMode::AbsoluteY,
+            cycles: 5,
+            illegal: true,
+        },
+        0x9c => Instruction {
+            opcode: Opcode::SHY,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 5,
+            illegal: true,
+        },
         0x9d => Instruction {
             opcode: Opcode::STA,
             mode: AddressingMode::AbsoluteX,
             cycles: 5,
             illegal: false,
         },
+        0x9e => Instruction {
+            opcode: Opcode::SHX,
+            mode: AddressingMode::AbsoluteY,
+            cycles: 5,
+            illegal: true,
+        },
+        0x9f => Instruction {
+            opcode: Opcode::SHA,
+            mode: AddressingMode::AbsoluteY,
+            cycles: 5,
+            illegal: true,
+        },
         0xa0 => Instruction {
             opcode: Opcode::LDY,
             mode: AddressingMode::Immediate,
@@ -743,6 +1134,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: false,
         },
+        0xa3 => Instruction {
+            opcode: Opcode::LAX,
+            mode: AddressingMode::IndirectX,
+            cycles: 6,
+            illegal: true,
+        },
         0xa4 => Instruction {
             opcode: Opcode::LDY,
             mode: AddressingMode::ZeroPage,
@@ -761,6 +1158,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 3,
             illegal: false,
         },
+        0xa7 => Instruction {
+            opcode: Opcode::LAX,
+            mode: AddressingMode::ZeroPage,
+            cycles: 3,
+            illegal: true,
+        },
         0xa8 => Instruction {
             opcode: Opcode::TAY,
             mode: AddressingMode::Implied,
@@ -779,6 +1182,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: false,
         },
+        0xab => Instruction {
+            opcode: Opcode::LXA,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
         0xac => Instruction {
             opcode: Opcode::LDY,
             mode: AddressingMode::Absolute,
@@ -797,6 +1206,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 4,
             illegal: false,
         },
+        0xaf => Instruction {
+            opcode: Opcode::LAX,
+            mode: AddressingMode::Absolute,
+            cycles: 4,
+            illegal: true,
+        },
         0xb0 => Instruction {
             opcode: Opcode::BCS,
             mode: AddressingMode::Relative,
@@ -809,6 +1224,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0xb2 => Instruction {
+            opcode: Opcode::KIL,
+            mode: AddressingMode::Implied,
+            cycles: 2,
+            illegal: true,
+        },
+        0xb3 => Instruction {
+            opcode: Opcode::LAX,
+            mode: AddressingMode::IndirectY,
+            cycles: 5,
+            illegal: true,
+        },
         0xb4 => Instruction {
             opcode: Opcode::LDY,
             mode: AddressingMode::ZeroPageX,
@@ -827,6 +1254,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 4,
             illegal: false,
         },
+        0xb7 => Instruction {
+            opcode: Opcode::LAX,
+            mode: AddressingMode::ZeroPageY,
+            cycles: 4,
+            illegal: true,
+        },
         0xb8 => Instruction {
             opcode: Opcode::CLV,
             mode: AddressingMode::Implied,
@@ -845,6 +1278,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: false,
         },
+        0xbb => Instruction {
+            opcode: Opcode::LAS,
+            mode: AddressingMode::AbsoluteY,
+            cycles: 4,
+            illegal: true,
+        },
         0xbc => Instruction {
             opcode: Opcode::LDY,
             mode: AddressingMode::AbsoluteX,
@@ -863,6 +1302,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 4,
             illegal: false,
         },
+        0xbf => Instruction {
+            opcode: Opcode::LAX,
+            mode: AddressingMode::AbsoluteY,
+            cycles: 4,
+            illegal: true,
+        },
         0xc0 => Instruction {
             opcode: Opcode::CPY,
             mode: AddressingMode::Immediate,
@@ -875,6 +1320,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0xc2 => Instruction {
+            opcode: Opcode::NOP,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
+        0xc3 => Instruction {
+            opcode: Opcode::DCP,
+            mode: AddressingMode::IndirectX,
+            cycles: 8,
+            illegal: true,
+        },
         0xc4 => Instruction {
             opcode: Opcode::CPY,
             mode: AddressingMode::ZeroPage,
@@ -893,6 +1350,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0xc7 => Instruction {
+            opcode: Opcode::DCP,
+            mode: AddressingMode::ZeroPage,
+            cycles: 5,
+            illegal: true,
+        },
         0xc8 => Instruction {
             opcode: Opcode::INY,
             mode: AddressingMode::Implied,
@@ -911,6 +1374,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: false,
         },
+        0xcb => Instruction {
+            opcode: Opcode::AXS,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
         0xcc => Instruction {
             opcode: Opcode::CPY,
             mode: AddressingMode::Absolute,
@@ -929,6 +1398,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0xcf => Instruction {
+            opcode: Opcode::DCP,
+            mode: AddressingMode::Absolute,
+            cycles: 6,
+            illegal: true,
+        },
         0xd0 => Instruction {
             opcode: Opcode::BNE,
             mode: AddressingMode::Relative,
@@ -941,6 +1416,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0xd2 => Instruction {
+            opcode: Opcode::KIL,
+            mode: AddressingMode::Implied,
+            cycles: 2,
+            illegal: true,
+        },
+        0xd3 => Instruction {
+            opcode: Opcode::DCP,
+            mode: AddressingMode::IndirectY,
+            cycles: 8,
+            illegal: true,
+        },
         0xd4 => Instruction {
             opcode: Opcode::NOP,
             mode: AddressingMode::ZeroPageX,
@@ -959,6 +1446,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0xd7 => Instruction {
+            opcode: Opcode::DCP,
+            mode: AddressingMode::ZeroPageX,
+            cycles: 6,
+            illegal: true,
+        },
         0xd8 => Instruction {
             opcode: Opcode::CLD,
             mode: AddressingMode::Implied,
@@ -977,6 +1470,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: true,
         },
+        0xdb => Instruction {
+            opcode: Opcode::DCP,
+            mode: AddressingMode::AbsoluteY,
+            cycles: 7,
+            illegal: true,
+        },
+        0xdc => Instruction {
+            opcode: Opcode::NOP,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 4,
+            illegal: true,
+        },
         0xdd => Instruction {
             opcode: Opcode::CMP,
             mode: AddressingMode::AbsoluteX,
@@ -989,6 +1494,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 7,
             illegal: false,
         },
+        0xdf => Instruction {
+            opcode: Opcode::DCP,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 7,
+            illegal: true,
+        },
         0xe0 => Instruction {
             opcode: Opcode::CPX,
             mode: AddressingMode::Immediate,
@@ -1001,6 +1512,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0xe2 => Instruction {
+            opcode: Opcode::NOP,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
+        0xe3 => Instruction {
+            opcode: Opcode::ISC,
+            mode: AddressingMode::IndirectX,
+            cycles: 8,
+            illegal: true,
+        },
         0xe4 => Instruction {
             opcode: Opcode::CPX,
             mode: AddressingMode::ZeroPage,
@@ -1019,6 +1542,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0xe7 => Instruction {
+            opcode: Opcode::ISC,
+            mode: AddressingMode::ZeroPage,
+            cycles: 5,
+            illegal: true,
+        },
         0xe8 => Instruction {
             opcode: Opcode::INX,
             mode: AddressingMode::Implied,
@@ -1037,6 +1566,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: false,
         },
+        0xeb => Instruction {
+            opcode: Opcode::SBC,
+            mode: AddressingMode::Immediate,
+            cycles: 2,
+            illegal: true,
+        },
         0xec => Instruction {
             opcode: Opcode::CPX,
             mode: AddressingMode::Absolute,
@@ -1055,6 +1590,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0xef => Instruction {
+            opcode: Opcode::ISC,
+            mode: AddressingMode::Absolute,
+            cycles: 6,
+            illegal: true,
+        },
         0xf0 => Instruction {
             opcode: Opcode::BEQ,
             mode: AddressingMode::Relative,
@@ -1067,6 +1608,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 5,
             illegal: false,
         },
+        0xf2 => Instruction {
+            opcode: Opcode::KIL,
+            mode: AddressingMode::Implied,
+            cycles: 2,
+            illegal: true,
+        },
+        0xf3 => Instruction {
+            opcode: Opcode::ISC,
+            mode: AddressingMode::IndirectY,
+            cycles: 8,
+            illegal: true,
+        },
         0xf4 => Instruction {
             opcode: Opcode::NOP,
             mode: AddressingMode::ZeroPageX,
@@ -1085,6 +1638,12 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 6,
             illegal: false,
         },
+        0xf7 => Instruction {
+            opcode: Opcode::ISC,
+            mode: AddressingMode::ZeroPageX,
+            cycles: 6,
+            illegal: true,
+        },
         0xf8 => Instruction {
             opcode: Opcode::SED,
             mode: AddressingMode::Implied,
@@ -1103,6 +1662,18 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 2,
             illegal: true,
         },
+        0xfb => Instruction {
+            opcode: Opcode::ISC,
+            mode: AddressingMode::AbsoluteY,
+            cycles: 7,
+            illegal: true,
+        },
+        0xfc => Instruction {
+            opcode: Opcode::NOP,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 4,
+            illegal: true,
+        },
         0xfd => Instruction {
             opcode: Opcode::SBC,
             mode: AddressingMode::AbsoluteX,
@@ -1115,6 +1686,11 @@ fn lookup_instruction(code: u8) -> Instruction {
             cycles: 7,
             illegal: false,
         },
-        _ => panic!("invalid instruction code {:02X}", code),
+        0xff => Instruction {
+            opcode: Opcode::ISC,
+            mode: AddressingMode::AbsoluteX,
+            cycles: 7,
+            illegal: true,
+        },
     }
 }
@@ -0,0 +1,685 @@
+mod condition;
+
+pub use self::condition::{Condition, ConditionError};
+
+use crate::bus::{AccessKind, AddressSpace, BusAccess};
+use crate::disasm::{disassemble, DisassembledInstruction};
+use crate::symbols::SymbolTable;
+use crate::Nes;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+const JSR: u8 = 0x20;
+const RTS: u8 = 0x60;
+const RTI: u8 = 0x40;
+
+// 10 seconds
+const DEFAULT_FRAME_LIMIT: u64 = 600;
+
+// Count a hit if the condition is met. True if the hit should stop execution.
+fn count_hit(condition: &Option<Condition>, ignore_count: u64, hits: &mut u64, nes: &Nes) -> bool {
+    if let Some(condition) = condition {
+        if !condition.is_met(nes) {
+            return false;
+        }
+    }
+    *hits += 1;
+    *hits > ignore_count
+}
+
+fn fmt_condition(
+    f: &mut fmt::Formatter,
+    condition: &Option<Condition>,
+    ignore_count: u64,
+    hits: u64,
+) -> fmt::Result {
+    if let Some(condition) = condition {
+        write!(f, " if {}", condition)?;
+    }
+    if ignore_count > 0 {
+        write!(f, " after {}", ignore_count)?;
+    }
+    write!(f, " (hits {})", hits)
+}
+
+/// Stop before executing the instruction at an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// instruction address
+    pub addr: u16,
+    /// stop only when the condition is met
+    pub condition: Option<Condition>,
+    /// number of hits to pass without stopping
+    pub ignore_count: u64,
+    hits: u64,
+}
+
+impl Breakpoint {
+    /// Unconditional breakpoint
+    pub fn new(addr: u16) -> Breakpoint {
+        Breakpoint {
+            addr,
+            condition: None,
+            ignore_count: 0,
+            hits: 0,
+        }
+    }
+
+    /// Number of times the address was reached with the condition met
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "${:04X}", self.addr)?;
+        fmt_condition(f, &self.condition, self.ignore_count, self.hits)
+    }
+}
+
+/// Bus a watchpoint observes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchTarget {
+    /// cpu addresses
+    Cpu,
+    /// PPU registers $2000-$2007 including their mirrors
+    PpuRegister,
+    /// ppu addresses accessed through $2007
+    Vram,
+}
+
+/// Accesses a watchpoint triggers on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    /// loads
+    Read,
+    /// stores
+    Write,
+    /// loads and stores
+    ReadWrite,
+    /// instruction fetches at the address. Only for cpu addresses.
+    Execute,
+}
+
+/// Stop when an address in the range is accessed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// bus to observe
+    pub target: WatchTarget,
+    /// accesses to observe
+    pub kind: WatchKind,
+    /// watched addresses. $2000-$2007 for PPU registers.
+    pub range: RangeInclusive<u16>,
+    /// stop only when the condition is met after the instruction
+    pub condition: Option<Condition>,
+    /// number of hits to pass without stopping
+    pub ignore_count: u64,
+    hits: u64,
+}
+
+impl Watchpoint {
+    /// Unconditional watchpoint
+    pub fn new(target: WatchTarget, kind: WatchKind, range: RangeInclusive<u16>) -> Watchpoint {
+        Watchpoint {
+            target,
+            kind,
+            range,
+            condition: None,
+            ignore_count: 0,
+            hits: 0,
+        }
+    }
+
+    /// Number of matched accesses with the condition met
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    fn matches_access(&self, access: &BusAccess) -> bool {
+        let kind_matches = matches!(
+            (self.kind, access.kind),
+            (WatchKind::Read, AccessKind::Read)
+                | (WatchKind::Write, AccessKind::Write)
+                | (WatchKind::ReadWrite, _)
+        );
+        let addr = match (self.target, access.space) {
+            (WatchTarget::Cpu, AddressSpace::Cpu) | (WatchTarget::Vram, AddressSpace::Vram) => {
+                access.addr
+            }
+            (WatchTarget::PpuRegister, AddressSpace::Cpu) => match access.addr {
+                0x2000..=0x3fff => 0x2000 | (access.addr & 0x07),
+                _ => return false,
+            },
+            _ => return false,
+        };
+        kind_matches && self.range.contains(&addr)
+    }
+
+    fn matches_execute(&self, pc: u16) -> bool {
+        self.target == WatchTarget::Cpu
+            && self.kind == WatchKind::Execute
+            && self.range.contains(&pc)
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let target = match self.target {
+            WatchTarget::Cpu => "cpu",
+            WatchTarget::PpuRegister => "ppu",
+            WatchTarget::Vram => "vram",
+        };
+        let kind = match self.kind {
+            WatchKind::Read => "r",
+            WatchKind::Write => "w",
+            WatchKind::ReadWrite => "rw",
+            WatchKind::Execute => "x",
+        };
+        write!(
+            f,
+            "{} {} ${:04X}-${:04X}",
+            target,
+            kind,
+            self.range.start(),
+            self.range.end()
+        )?;
+        fmt_condition(f, &self.condition, self.ignore_count, self.hits)
+    }
+}
+
+/// Why the debugger returned control
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// requested step finished
+    Step,
+    /// PC reached a breakpoint
+    Breakpoint(u16),
+    /// a watchpoint observed an access. `access` is None for execute watchpoints.
+    Watchpoint {
+        /// id returned by `add_watchpoint`
+        id: usize,
+        /// the access which triggered the watchpoint
+        access: Option<BusAccess>,
+    },
+    /// PPU entered the scanline
+    Scanline(u16),
+    /// the frame limit passed without stopping
+    FrameLimit,
+    /// CPU locked up by KIL instruction
+    Halted,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::Step => write!(f, "step"),
+            StopReason::Breakpoint(addr) => write!(f, "breakpoint at ${:04X}", addr),
+            StopReason::Watchpoint { id, access: None } => write!(f, "watchpoint #{}", id),
+            StopReason::Watchpoint {
+                id,
+                access: Some(access),
+            } => {
+                let space = match access.space {
+                    AddressSpace::Cpu => "",
+                    AddressSpace::Vram => "vram ",
+                };
+                let kind = match access.kind {
+                    AccessKind::Read => "read",
+                    AccessKind::Write => "write",
+                };
+                write!(
+                    f,
+                    "watchpoint #{}: {} {}${:04X} = ${:02X}",
+                    id, kind, space, access.addr, access.value
+                )
+            }
+            StopReason::Scanline(scanline) => write!(f, "scanline {}", scanline),
+            StopReason::FrameLimit => write!(f, "frame limit"),
+            StopReason::Halted => write!(f, "cpu halted"),
+        }
+    }
+}
+
+/// Runs NES instruction by instruction and stops at breakpoints and watchpoints
+pub struct Debugger {
+    nes: Nes,
+    breakpoints: BTreeMap<u16, Breakpoint>,
+    watchpoints: BTreeMap<usize, Watchpoint>,
+    next_watchpoint_id: usize,
+    frame_limit: u64,
+    access_log: Vec<BusAccess>,
+    symbols: Rc<SymbolTable>,
+}
+
+impl Debugger {
+    /// Debug the NES
+    pub fn new(nes: Nes) -> Debugger {
+        Debugger {
+            nes,
+            breakpoints: BTreeMap::new(),
+            watchpoints: BTreeMap::new(),
+            next_watchpoint_id: 0,
+            frame_limit: DEFAULT_FRAME_LIMIT,
+            access_log: Vec::new(),
+            symbols: Rc::new(SymbolTable::new()),
+        }
+    }
+
+    /// Debugged NES
+    pub fn nes(&self) -> &Nes {
+        &self.nes
+    }
+
+    /// Debugged NES
+    pub fn nes_mut(&mut self) -> &mut Nes {
+        &mut self.nes
+    }
+
+    /// Stop debugging and return the NES
+    pub fn into_nes(self) -> Nes {
+        self.nes
+    }
+
+    /// Byte at cpu address without side effects
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.nes.peek_cpu(addr)
+    }
+
+    /// Decode the instruction at cpu address
+    pub fn disassemble(&self, addr: u16) -> DisassembledInstruction {
+        disassemble(&self.nes, addr)
+    }
+
+    /// Name addresses with the symbols
+    pub fn set_symbols(&mut self, symbols: Rc<SymbolTable>) {
+        self.symbols = symbols;
+    }
+
+    /// Loaded symbols. Empty if none is set.
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// `name` or `name+offset` for the cpu address in the current bank
+    pub fn label(&self, addr: u16) -> Option<String> {
+        self.symbols.label(addr, &self.nes)
+    }
+
+    /// Format an instruction with labels
+    pub fn format_instruction(&self, inst: &DisassembledInstruction) -> String {
+        inst.format_with(&|addr| self.label(addr))
+    }
+
+    /// Stop at `name` or `name+offset`. Returns the address, or None if the symbol is unknown.
+    pub fn add_symbol_breakpoint(&mut self, expr: &str) -> Option<u16> {
+        let addr = self.symbols.resolve(expr)?;
+        self.add_breakpoint(Breakpoint::new(addr));
+        Some(addr)
+    }
+
+    /// Give up running after this many frames. Running never blocks forever.
+    pub fn set_frame_limit(&mut self, frames: u64) {
+        self.frame_limit = frames;
+    }
+
+    /// Replaces the breakpoint at the same address
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.insert(breakpoint.addr, breakpoint);
+    }
+
+    /// Returns false if there was no breakpoint at the address
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr).is_some()
+    }
+
+    /// Breakpoints in ascending order of address
+    pub fn breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.values()
+    }
+
+    /// Returns id to remove the watchpoint
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        let id = self.next_watchpoint_id;
+        self.next_watchpoint_id += 1;
+        self.watchpoints.insert(id, watchpoint);
+        id
+    }
+
+    /// Returns false if there was no watchpoint with the id
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        self.watchpoints.remove(&id).is_some()
+    }
+
+    /// Watchpoints with their ids
+    pub fn watchpoints(&self) -> impl Iterator<Item = (usize, &Watchpoint)> {
+        self.watchpoints.iter().map(|(id, w)| (*id, w))
+    }
+
+    /// Execute a single instruction
+    pub fn step(&mut self) -> StopReason {
+        self.run_until(|_, _| Some(StopReason::Step))
+    }
+
+    /// Execute a single instruction. A JSR runs until the subroutine returns.
+    pub fn step_over(&mut self) -> StopReason {
+        let reg = self.nes.cpu_registers();
+        let inst = self.disassemble(reg.pc);
+        if inst.bytes[0] != JSR {
+            return self.step();
+        }
+        let return_addr = inst.next_addr();
+        self.run_until(|nes, _| {
+            let current = nes.cpu_registers();
+            if current.pc == return_addr && current.s == reg.s {
+                Some(StopReason::Step)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Run until RTS or RTI returns from the current subroutine or interrupt handler
+    pub fn step_out(&mut self) -> StopReason {
+        let s = self.nes.cpu_registers().s;
+        self.run_until(|nes, opcode| {
+            if (opcode == RTS || opcode == RTI) && nes.cpu_registers().s > s {
+                Some(StopReason::Step)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Run until PPU enters the scanline. 261 is the pre-render line.
+    pub fn run_to_scanline(&mut self, scanline: u16) -> StopReason {
+        let mut prev = self.nes.ppu_position().0;
+        self.run_until(|nes, _| {
+            let (current, _) = nes.ppu_position();
+            let entered = current == scanline && prev != scanline;
+            prev = current;
+            if entered {
+                Some(StopReason::Scanline(scanline))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Run until a breakpoint or a watchpoint hits
+    pub fn resume(&mut self) -> StopReason {
+        self.run_until(|_, _| None)
+    }
+
+    // Execute instructions until `done` returns a reason.
+    // `done` receives the opcode of the executed instruction.
+    // The instruction at the current PC is executed even if it has a breakpoint.
+    fn run_until<F>(&mut self, mut done: F) -> StopReason
+    where
+        F: FnMut(&Nes, u8) -> Option<StopReason>,
+    {
+        let start_frame = self.nes.frame_count();
+        loop {
+            if self.nes.is_cpu_halted() {
+                return StopReason::Halted;
+            }
+            let opcode = self.peek(self.nes.cpu_registers().pc);
+            if let Some(reason) = self.execute() {
+                return reason;
+            }
+            if let Some(reason) = done(&self.nes, opcode) {
+                return reason;
+            }
+            if let Some(reason) = self.check_execute() {
+                return reason;
+            }
+            if self.nes.frame_count() - start_frame >= self.frame_limit {
+                return StopReason::FrameLimit;
+            }
+        }
+    }
+
+    // Execute an instruction and check accesses against watchpoints
+    fn execute(&mut self) -> Option<StopReason> {
+        if self.watchpoints.is_empty() {
+            self.nes.step();
+            return None;
+        }
+        self.access_log.clear();
+        self.nes.step_with_access_log(&mut self.access_log);
+        for access in &self.access_log {
+            for (id, w) in self.watchpoints.iter_mut() {
+                if w.matches_access(access)
+                    && count_hit(&w.condition, w.ignore_count, &mut w.hits, &self.nes)
+                {
+                    return Some(StopReason::Watchpoint {
+                        id: *id,
+                        access: Some(*access),
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    // Check the next instruction against breakpoints and execute watchpoints
+    fn check_execute(&mut self) -> Option<StopReason> {
+        let pc = self.nes.cpu_registers().pc;
+        if let Some(b) = self.breakpoints.get_mut(&pc) {
+            if count_hit(&b.condition, b.ignore_count, &mut b.hits, &self.nes) {
+                return Some(StopReason::Breakpoint(pc));
+            }
+        }
+        for (id, w) in self.watchpoints.iter_mut() {
+            if w.matches_execute(pc)
+                && count_hit(&w.condition, w.ignore_count, &mut w.hits, &self.nes)
+            {
+                return Some(StopReason::Watchpoint {
+                    id: *id,
+                    access: None,
+                });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cartridge;
+
+    // NROM cartridge running `code` from $8000 with BRK/IRQ handler `handler` at $8020
+    fn debugger_running(code: &[u8], handler: &[u8]) -> Debugger {
+        let mut prg = vec![0xea; 0x8000];
+        prg[..code.len()].copy_from_slice(code);
+        prg[0x20..0x20 + handler.len()].copy_from_slice(handler);
+        prg[0x7ffc] = 0x00;
+        prg[0x7ffd] = 0x80;
+        prg[0x7ffe] = 0x20;
+        prg[0x7fff] = 0x80;
+        Debugger::new(Nes::with_cartridge(Cartridge::nrom(&prg)))
+    }
+
+    fn pc(debugger: &Debugger) -> u16 {
+        debugger.nes().cpu_registers().pc
+    }
+
+    // JSR $8020, then loop forever
+    const CALL: [u8; 6] = [0x20, 0x20, 0x80, 0x4c, 0x03, 0x80];
+    // INX, INX, RTS
+    const SUBROUTINE: [u8; 3] = [0xe8, 0xe8, 0x60];
+
+    #[test]
+    fn step_over_runs_the_whole_subroutine() {
+        let mut debugger = debugger_running(&CALL, &SUBROUTINE);
+        assert_eq!(debugger.step_over(), StopReason::Step);
+        assert_eq!(pc(&debugger), 0x8003);
+        assert_eq!(debugger.nes().cpu_registers().x, 2);
+        // other instructions are a single step
+        assert_eq!(debugger.step_over(), StopReason::Step);
+        assert_eq!(pc(&debugger), 0x8003);
+    }
+
+    #[test]
+    fn step_over_stops_at_breakpoints_in_the_subroutine() {
+        let mut debugger = debugger_running(&CALL, &SUBROUTINE);
+        debugger.add_breakpoint(Breakpoint::new(0x8021));
+        assert_eq!(debugger.step_over(), StopReason::Breakpoint(0x8021));
+        assert_eq!(debugger.nes().cpu_registers().x, 1);
+    }
+
+    #[test]
+    fn step_out_returns_through_rts() {
+        let mut debugger = debugger_running(&CALL, &SUBROUTINE);
+        debugger.step();
+        debugger.step();
+        assert_eq!(pc(&debugger), 0x8021);
+        assert_eq!(debugger.step_out(), StopReason::Step);
+        assert_eq!(pc(&debugger), 0x8003);
+        assert_eq!(debugger.nes().cpu_registers().x, 2);
+    }
+
+    #[test]
+    fn step_out_returns_through_rti() {
+        // BRK skips a padding byte. The handler is INX, RTI.
+        let mut debugger = debugger_running(&[0x00, 0xea], &[0xe8, 0x40]);
+        debugger.step();
+        assert_eq!(pc(&debugger), 0x8020);
+        assert_eq!(debugger.step_out(), StopReason::Step);
+        assert_eq!(pc(&debugger), 0x8002);
+        assert_eq!(debugger.nes().cpu_registers().x, 1);
+    }
+
+    #[test]
+    fn cpu_watchpoints() {
+        let code = [
+            0xa9, 0x5a, // LDA #$5A
+            0x8d, 0x20, 0x00, // STA $0020
+            0xad, 0x20, 0x00, // LDA $0020
+            0xea, // NOP
+            0xea, // NOP
+        ];
+        let mut debugger = debugger_running(&code, &[]);
+        let read = debugger.add_watchpoint(Watchpoint::new(
+            WatchTarget::Cpu,
+            WatchKind::Read,
+            0x0020..=0x0020,
+        ));
+        let write = debugger.add_watchpoint(Watchpoint::new(
+            WatchTarget::Cpu,
+            WatchKind::Write,
+            0x0020..=0x0021,
+        ));
+        let execute = debugger.add_watchpoint(Watchpoint::new(
+            WatchTarget::Cpu,
+            WatchKind::Execute,
+            0x8009..=0x8009,
+        ));
+        let access = |addr, kind| BusAccess {
+            space: AddressSpace::Cpu,
+            addr,
+            kind,
+            value: 0x5a,
+        };
+
+        assert_eq!(
+            debugger.resume(),
+            StopReason::Watchpoint {
+                id: write,
+                access: Some(access(0x0020, AccessKind::Write)),
+            }
+        );
+        assert_eq!(pc(&debugger), 0x8005);
+        assert_eq!(
+            debugger.resume(),
+            StopReason::Watchpoint {
+                id: read,
+                access: Some(access(0x0020, AccessKind::Read)),
+            }
+        );
+        assert_eq!(pc(&debugger), 0x8008);
+        // stops before executing the second NOP
+        assert_eq!(
+            debugger.resume(),
+            StopReason::Watchpoint {
+                id: execute,
+                access: None,
+            }
+        );
+        assert_eq!(pc(&debugger), 0x8009);
+        assert_eq!(
+            debugger.watchpoints().map(|(_, w)| w.hits()).sum::<u64>(),
+            3
+        );
+    }
+
+    #[test]
+    fn ppu_register_and_vram_watchpoints() {
+        let code = [
+            0xa9, 0x21, // LDA #$21
+            0x8d, 0x06, 0x20, // STA $2006
+            0xa9, 0x08, // LDA #$08
+            0x8d, 0xfe, 0x3f, // STA $3FFE (mirror of $2006)
+            0xa9, 0x77, // LDA #$77
+            0x8d, 0x07, 0x20, // STA $2007
+        ];
+        let mut debugger = debugger_running(&code, &[]);
+        let mut register =
+            Watchpoint::new(WatchTarget::PpuRegister, WatchKind::Write, 0x2006..=0x2006);
+        register.ignore_count = 1;
+        let register = debugger.add_watchpoint(register);
+        let vram = debugger.add_watchpoint(Watchpoint::new(
+            WatchTarget::Vram,
+            WatchKind::ReadWrite,
+            0x2100..=0x21ff,
+        ));
+
+        assert_eq!(
+            debugger.resume(),
+            StopReason::Watchpoint {
+                id: register,
+                access: Some(BusAccess {
+                    space: AddressSpace::Cpu,
+                    addr: 0x3ffe,
+                    kind: AccessKind::Write,
+                    value: 0x08,
+                }),
+            }
+        );
+        assert_eq!(
+            debugger.resume(),
+            StopReason::Watchpoint {
+                id: vram,
+                access: Some(BusAccess {
+                    space: AddressSpace::Vram,
+                    addr: 0x2108,
+                    kind: AccessKind::Write,
+                    value: 0x77,
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn run_to_scanline_stops_when_the_line_starts() {
+        // JMP $8000
+        let mut debugger = debugger_running(&[0x4c, 0x00, 0x80], &[]);
+        assert_eq!(debugger.run_to_scanline(100), StopReason::Scanline(100));
+        let (scanline, dot) = debugger.nes().ppu_position();
+        assert_eq!(scanline, 100);
+        // within the instruction which crossed into the line
+        assert!(dot < 3 * 7);
+    }
+
+    #[test]
+    fn frame_limit_stops_running() {
+        let mut debugger = debugger_running(&[0x4c, 0x00, 0x80], &[]);
+        debugger.set_frame_limit(2);
+        let start = debugger.nes().frame_count();
+        assert_eq!(debugger.resume(), StopReason::FrameLimit);
+        assert_eq!(debugger.nes().frame_count() - start, 2);
+    }
+}
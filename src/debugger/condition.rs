@@ -0,0 +1,540 @@
+use crate::Nes;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Error on parsing a condition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConditionError {
+    /// character which can't start a token
+    UnexpectedChar {
+        /// byte offset in the source
+        position: usize,
+        /// the character
+        found: char,
+    },
+    /// token in a wrong place
+    UnexpectedToken {
+        /// byte offset in the source
+        position: usize,
+        /// the token
+        found: String,
+    },
+    /// source ends in the middle of an expression
+    UnexpectedEnd,
+    /// name which is not a register, flag or PPU counter
+    UnknownName {
+        /// byte offset in the source
+        position: usize,
+        /// the name
+        name: String,
+    },
+    /// number literal which can't be parsed
+    InvalidNumber {
+        /// byte offset in the source
+        position: usize,
+        /// the literal
+        text: String,
+    },
+}
+
+impl fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConditionError::UnexpectedChar { position, found } => {
+                write!(f, "unexpected '{}' at {}", found, position)
+            }
+            ConditionError::UnexpectedToken { position, found } => {
+                write!(f, "unexpected '{}' at {}", found, position)
+            }
+            ConditionError::UnexpectedEnd => write!(f, "unexpected end of condition"),
+            ConditionError::UnknownName { position, name } => {
+                write!(f, "unknown name '{}' at {}", name, position)
+            }
+            ConditionError::InvalidNumber { position, text } => {
+                write!(f, "invalid number '{}' at {}", text, position)
+            }
+        }
+    }
+}
+
+impl Error for ConditionError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Variable {
+    A,
+    X,
+    Y,
+    P,
+    S,
+    Pc,
+    // mask in the status register
+    Flag(u8),
+    Scanline,
+    Dot,
+    Frame,
+    Cycles,
+}
+
+impl Variable {
+    fn from_name(name: &str) -> Option<Variable> {
+        let variable = match name.to_ascii_lowercase().as_str() {
+            "a" => Variable::A,
+            "x" => Variable::X,
+            "y" => Variable::Y,
+            "p" => Variable::P,
+            "s" | "sp" => Variable::S,
+            "pc" => Variable::Pc,
+            "n" => Variable::Flag(0x80),
+            "v" => Variable::Flag(0x40),
+            "d" => Variable::Flag(0x08),
+            "i" => Variable::Flag(0x04),
+            "z" => Variable::Flag(0x02),
+            "c" => Variable::Flag(0x01),
+            "scanline" => Variable::Scanline,
+            "dot" => Variable::Dot,
+            "frame" => Variable::Frame,
+            "cycles" => Variable::Cycles,
+            _ => return None,
+        };
+        Some(variable)
+    }
+
+    fn evaluate(self, nes: &Nes) -> i64 {
+        let reg = nes.cpu_registers();
+        match self {
+            Variable::A => i64::from(reg.a),
+            Variable::X => i64::from(reg.x),
+            Variable::Y => i64::from(reg.y),
+            Variable::P => i64::from(reg.p),
+            Variable::S => i64::from(reg.s),
+            Variable::Pc => i64::from(reg.pc),
+            Variable::Flag(mask) => i64::from(reg.p & mask != 0),
+            Variable::Scanline => i64::from(nes.ppu_position().0),
+            Variable::Dot => i64::from(nes.ppu_position().1),
+            Variable::Frame => nes.frame_count() as i64,
+            Variable::Cycles => nes.cycles() as i64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Sub,
+}
+
+impl BinaryOp {
+    // Same as Rust. Bitwise operators bind tighter than comparisons.
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge => 3,
+            BinaryOp::BitOr => 4,
+            BinaryOp::BitXor => 5,
+            BinaryOp::BitAnd => 6,
+            BinaryOp::Add | BinaryOp::Sub => 7,
+        }
+    }
+
+    fn apply(self, lhs: i64, rhs: i64) -> i64 {
+        match self {
+            BinaryOp::Or => i64::from(lhs != 0 || rhs != 0),
+            BinaryOp::And => i64::from(lhs != 0 && rhs != 0),
+            BinaryOp::Eq => i64::from(lhs == rhs),
+            BinaryOp::Ne => i64::from(lhs != rhs),
+            BinaryOp::Lt => i64::from(lhs < rhs),
+            BinaryOp::Le => i64::from(lhs <= rhs),
+            BinaryOp::Gt => i64::from(lhs > rhs),
+            BinaryOp::Ge => i64::from(lhs >= rhs),
+            BinaryOp::BitOr => lhs | rhs,
+            BinaryOp::BitXor => lhs ^ rhs,
+            BinaryOp::BitAnd => lhs & rhs,
+            BinaryOp::Add => lhs.wrapping_add(rhs),
+            BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Number(i64),
+    Variable(Variable),
+    // byte at cpu address
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn evaluate(&self, nes: &Nes) -> i64 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Variable(v) => v.evaluate(nes),
+            Expr::Memory(addr) => i64::from(nes.peek_cpu(addr.evaluate(nes) as u16)),
+            Expr::Not(e) => i64::from(e.evaluate(nes) == 0),
+            Expr::Binary(op, lhs, rhs) => op.apply(lhs.evaluate(nes), rhs.evaluate(nes)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Binary(BinaryOp),
+    Not,
+    OpenParen,
+    CloseParen,
+    OpenBracket,
+    CloseBracket,
+}
+
+const OPERATORS: [(&str, Token); 18] = [
+    ("||", Token::Binary(BinaryOp::Or)),
+    ("&&", Token::Binary(BinaryOp::And)),
+    ("==", Token::Binary(BinaryOp::Eq)),
+    ("!=", Token::Binary(BinaryOp::Ne)),
+    ("<=", Token::Binary(BinaryOp::Le)),
+    (">=", Token::Binary(BinaryOp::Ge)),
+    ("<", Token::Binary(BinaryOp::Lt)),
+    (">", Token::Binary(BinaryOp::Gt)),
+    ("|", Token::Binary(BinaryOp::BitOr)),
+    ("^", Token::Binary(BinaryOp::BitXor)),
+    ("&", Token::Binary(BinaryOp::BitAnd)),
+    ("+", Token::Binary(BinaryOp::Add)),
+    ("-", Token::Binary(BinaryOp::Sub)),
+    ("!", Token::Not),
+    ("(", Token::OpenParen),
+    (")", Token::CloseParen),
+    ("[", Token::OpenBracket),
+    ("]", Token::CloseBracket),
+];
+
+// Returns tokens with their byte offsets
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ConditionError> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let position = source.len() - rest.len();
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        if let Some((op, token)) = OPERATORS.iter().find(|(op, _)| rest.starts_with(op)) {
+            tokens.push((position, token.clone()));
+            rest = &rest[op.len()..];
+            continue;
+        }
+        if !c.is_ascii_alphanumeric() && c != '$' && c != '%' && c != '_' {
+            return Err(ConditionError::UnexpectedChar { position, found: c });
+        }
+        let len = rest[1..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .map_or(rest.len(), |i| i + 1);
+        let word = &rest[..len];
+        rest = &rest[len..];
+        let token = if c.is_ascii_alphabetic() || c == '_' {
+            Token::Name(word.to_string())
+        } else {
+            Token::Number(
+                parse_number(word).ok_or_else(|| ConditionError::InvalidNumber {
+                    position,
+                    text: word.to_string(),
+                })?,
+            )
+        };
+        tokens.push((position, token));
+    }
+    Ok(tokens)
+}
+
+// `$ff` and `0xff` are hexadecimal, `%101` is binary, others are decimal
+fn parse_number(word: &str) -> Option<i64> {
+    let (digits, radix) = if let Some(digits) = word.strip_prefix('$') {
+        (digits, 16)
+    } else if let Some(digits) = word.strip_prefix("0x") {
+        (digits, 16)
+    } else if let Some(digits) = word.strip_prefix('%') {
+        (digits, 2)
+    } else {
+        (word, 10)
+    };
+    i64::from_str_radix(digits, radix).ok()
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), ConditionError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or(ConditionError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn unexpected(&self, position: usize) -> ConditionError {
+        let end = self
+            .tokens
+            .iter()
+            .map(|(p, _)| *p)
+            .find(|p| *p > position)
+            .unwrap_or(self.source.len());
+        ConditionError::UnexpectedToken {
+            position,
+            found: self.source[position..end].trim().to_string(),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ConditionError> {
+        let (position, token) = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(self.unexpected(position))
+        }
+    }
+
+    // Precedence climbing over binary operators
+    fn parse_expr(&mut self, min_precedence: u8) -> Result<Expr, ConditionError> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(Token::Binary(op)) = self.peek() {
+            let op = *op;
+            if op.precedence() < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.parse_expr(op.precedence() + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ConditionError> {
+        let (position, token) = self.next()?;
+        match token {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Name(name) => Variable::from_name(&name)
+                .map(Expr::Variable)
+                .ok_or(ConditionError::UnknownName { position, name }),
+            Token::Not => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            Token::OpenParen => {
+                let expr = self.parse_expr(0)?;
+                self.expect(Token::CloseParen)?;
+                Ok(expr)
+            }
+            Token::OpenBracket => {
+                let addr = self.parse_expr(0)?;
+                self.expect(Token::CloseBracket)?;
+                Ok(Expr::Memory(Box::new(addr)))
+            }
+            _ => Err(self.unexpected(position)),
+        }
+    }
+}
+
+/// Expression evaluated when a breakpoint or a watchpoint is hit, e.g.
+/// `A == $40 && [$0300] > 3 && scanline < 20`.
+///
+/// Names are registers `a x y p s pc`, flags `n v d i z c`,
+/// `scanline`, `dot`, `frame` and `cycles`. `[addr]` reads a byte without side effects.
+/// Operators are `|| && == != < <= > >= | ^ & + - !` with Rust precedence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    /// Parse the expression
+    pub fn parse(source: &str) -> Result<Condition, ConditionError> {
+        let mut parser = Parser {
+            source,
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let expr = parser.parse_expr(0)?;
+        if let Some((position, _)) = parser.tokens.get(parser.pos) {
+            return Err(parser.unexpected(*position));
+        }
+        Ok(Condition {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+
+    /// Value of the expression. Comparisons are 1 or 0.
+    pub fn evaluate(&self, nes: &Nes) -> i64 {
+        self.expr.evaluate(nes)
+    }
+
+    /// True if the value is not 0
+    pub fn is_met(&self, nes: &Nes) -> bool {
+        self.evaluate(nes) != 0
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ConditionError;
+
+    fn from_str(s: &str) -> Result<Condition, ConditionError> {
+        Condition::parse(s)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cartridge;
+
+    // NROM cartridge running `code` from $8000
+    fn nes_running(code: &[u8]) -> Nes {
+        let mut prg = vec![0xea; 0x8000];
+        prg[..code.len()].copy_from_slice(code);
+        prg[0x7ffc] = 0x00;
+        prg[0x7ffd] = 0x80;
+        Nes::with_cartridge(Cartridge::nrom(&prg))
+    }
+
+    fn number(n: i64) -> Box<Expr> {
+        Box::new(Expr::Number(n))
+    }
+
+    fn variable(v: Variable) -> Box<Expr> {
+        Box::new(Expr::Variable(v))
+    }
+
+    fn binary(op: BinaryOp, lhs: Box<Expr>, rhs: Box<Expr>) -> Box<Expr> {
+        Box::new(Expr::Binary(op, lhs, rhs))
+    }
+
+    fn parse_expr(source: &str) -> Expr {
+        Condition::parse(source).unwrap().expr
+    }
+
+    #[test]
+    fn bitwise_operators_bind_tighter_than_comparisons() {
+        let expected = binary(
+            BinaryOp::Eq,
+            binary(BinaryOp::BitOr, number(1), number(2)),
+            number(3),
+        );
+        assert_eq!(parse_expr("1 | 2 == 3"), *expected);
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let eq = |v, n| binary(BinaryOp::Eq, variable(v), number(n));
+        let expected = binary(
+            BinaryOp::Or,
+            eq(Variable::A, 1),
+            binary(BinaryOp::And, eq(Variable::X, 2), eq(Variable::Y, 3)),
+        );
+        assert_eq!(parse_expr("a == 1 || x == 2 && y == 3"), *expected);
+    }
+
+    #[test]
+    fn number_literals() {
+        assert_eq!(parse_expr("$ff"), Expr::Number(0xff));
+        assert_eq!(parse_expr("$C000"), Expr::Number(0xc000));
+        assert_eq!(parse_expr("0x10"), Expr::Number(0x10));
+        assert_eq!(parse_expr("%101"), Expr::Number(5));
+        assert_eq!(parse_expr("42"), Expr::Number(42));
+    }
+
+    #[test]
+    fn memory_reads_and_registers() {
+        // LDA #$5A; STA $0300; LDX #$02; LDY #$03
+        let mut nes = nes_running(&[0xa9, 0x5a, 0x8d, 0x00, 0x03, 0xa2, 0x02, 0xa0, 0x03]);
+        for _ in 0..4 {
+            nes.step();
+        }
+        let evaluate = |source: &str| Condition::parse(source).unwrap().evaluate(&nes);
+        assert_eq!(evaluate("[$0300]"), 0x5a);
+        assert_eq!(evaluate("[$02ff + 1] == $5a"), 1);
+        assert_eq!(evaluate("[$0300 + x - 2]"), 0x5a);
+        assert_eq!(evaluate("a == $5a && x == 2 && y == 3"), 1);
+        assert_eq!(evaluate("pc"), 0x8009);
+    }
+
+    #[test]
+    fn flags() {
+        // LDA #$00; LDX #$80
+        let mut nes = nes_running(&[0xa9, 0x00, 0xa2, 0x80]);
+        nes.step();
+        let is_met = |nes: &Nes, source: &str| Condition::parse(source).unwrap().is_met(nes);
+        assert!(is_met(&nes, "z && !n"));
+        assert!(is_met(&nes, "Z == 1"));
+        nes.step();
+        assert!(is_met(&nes, "n && !z"));
+        assert!(is_met(&nes, "p & $80 == $80"));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Condition::parse("A =="), Err(ConditionError::UnexpectedEnd));
+        assert_eq!(Condition::parse("(1"), Err(ConditionError::UnexpectedEnd));
+        assert_eq!(
+            Condition::parse("foo"),
+            Err(ConditionError::UnknownName {
+                position: 0,
+                name: "foo".to_string()
+            })
+        );
+        assert_eq!(
+            Condition::parse("a == $zz"),
+            Err(ConditionError::InvalidNumber {
+                position: 5,
+                text: "$zz".to_string()
+            })
+        );
+        assert_eq!(
+            Condition::parse("(1 2"),
+            Err(ConditionError::UnexpectedToken {
+                position: 3,
+                found: "2".to_string()
+            })
+        );
+        assert_eq!(
+            Condition::parse("a # 1"),
+            Err(ConditionError::UnexpectedChar {
+                position: 2,
+                found: '#'
+            })
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use crate::cartridge::Cartridge;
+use crate::cpu::instructions::{AddressingMode, Instruction, Opcode};
+use crate::cpu::Registers;
+use crate::symbols::SymbolTable;
+use crate::Nes;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Memory the disassembler reads from. Reads must not have side effects.
+pub trait MemoryView {
+    /// Byte at cpu address. What unmapped addresses return is up to the implementation:
+    /// `Nes` returns the open bus value, `Cartridge` and `PrgBank` return 0.
+    fn peek(&self, addr: u16) -> u8;
+
+    /// Offset in PRG ROM mapped at cpu address, if known. Used to find labels in banks.
+    fn prg_rom_offset(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+}
+
+impl MemoryView for Cartridge {
+    fn peek(&self, addr: u16) -> u8 {
+        self.cpu_load(addr).unwrap_or(0)
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        Cartridge::prg_rom_offset(self, addr)
+    }
+}
+
+impl MemoryView for Nes {
+    fn peek(&self, addr: u16) -> u8 {
+        self.peek_cpu(addr)
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.cartridge().prg_rom_offset(addr)
+    }
+}
+
+impl<F: Fn(u16) -> u8> MemoryView for F {
+    fn peek(&self, addr: u16) -> u8 {
+        self(addr)
+    }
+}
+
+/// A PRG ROM bank mapped at a cpu address, regardless of the current mapper state
+pub struct PrgBank<'a> {
+    data: &'a [u8],
+    base: u16,
+}
+
+impl<'a> PrgBank<'a> {
+    /// Map `data` at `base`. e.g. `PrgBank::new(&cartridge.prg_rom[0..0x4000], 0x8000)`
+    pub fn new(data: &'a [u8], base: u16) -> PrgBank<'a> {
+        PrgBank { data, base }
+    }
+}
+
+impl<'a> MemoryView for PrgBank<'a> {
+    fn peek(&self, addr: u16) -> u8 {
+        addr.checked_sub(self.base)
+            .and_then(|offset| self.data.get(usize::from(offset)))
+            .cloned()
+            .unwrap_or(0)
+    }
+}
+
+/// Operand of a decoded instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// no operand
+    Implied,
+    /// `A`
+    Accumulator,
+    /// `#$nn`
+    Immediate(u8),
+    /// `$nn`
+    ZeroPage(u8),
+    /// `$nn,X`
+    ZeroPageX(u8),
+    /// `$nn,Y`
+    ZeroPageY(u8),
+    /// `$nnnn`
+    Absolute(u16),
+    /// `$nnnn,X`
+    AbsoluteX(u16),
+    /// `$nnnn,Y`
+    AbsoluteY(u16),
+    /// `($nnnn)`
+    Indirect(u16),
+    /// `($nn,X)`
+    IndirectX(u8),
+    /// `($nn),Y`
+    IndirectY(u8),
+    /// branch target address
+    Relative(u16),
+}
+
+/// Decoded instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassembledInstruction {
+    /// address of the opcode
+    pub addr: u16,
+    /// opcode followed by operand bytes
+    pub bytes: Vec<u8>,
+    /// e.g. `LDA`
+    pub mnemonic: String,
+    /// true for unofficial opcodes
+    pub unofficial: bool,
+    /// decoded operand
+    pub operand: Operand,
+    /// memory address the instruction accesses. Only set by `resolve`.
+    pub effective_addr: Option<u16>,
+}
+
+impl DisassembledInstruction {
+    /// Address of the next instruction
+    pub fn next_addr(&self) -> u16 {
+        self.addr.wrapping_add(self.bytes.len() as u16)
+    }
+
+    /// Branch or jump destination known without registers
+    pub fn target(&self) -> Option<u16> {
+        match (self.mnemonic.as_str(), self.operand) {
+            (_, Operand::Relative(addr)) => Some(addr),
+            ("JMP", Operand::Absolute(addr)) | ("JSR", Operand::Absolute(addr)) => Some(addr),
+            _ => None,
+        }
+    }
+
+    /// Compute the address accessed with the registers and memory at the time of execution
+    pub fn resolve(&mut self, reg: &Registers, memory: &impl MemoryView) {
+        let zero_page_w = |addr: u8| {
+            u16::from(memory.peek(u16::from(addr)))
+                | (u16::from(memory.peek(u16::from(addr.wrapping_add(1)))) << 8)
+        };
+        self.effective_addr = match self.operand {
+            Operand::Implied | Operand::Accumulator | Operand::Immediate(_) => None,
+            Operand::ZeroPage(addr) => Some(u16::from(addr)),
+            Operand::ZeroPageX(addr) => Some(u16::from(addr.wrapping_add(reg.x))),
+            Operand::ZeroPageY(addr) => Some(u16::from(addr.wrapping_add(reg.y))),
+            Operand::Absolute(addr) | Operand::Relative(addr) => Some(addr),
+            Operand::AbsoluteX(addr) => Some(addr.wrapping_add(u16::from(reg.x))),
+            Operand::AbsoluteY(addr) => Some(addr.wrapping_add(u16::from(reg.y))),
+            Operand::Indirect(addr) => {
+                // JMP ($xxFF) doesn't carry into the high byte
+                let next = (addr & 0xff00) | (addr.wrapping_add(1) & 0x00ff);
+                Some(u16::from(memory.peek(addr)) | (u16::from(memory.peek(next)) << 8))
+            }
+            Operand::IndirectX(addr) => Some(zero_page_w(addr.wrapping_add(reg.x))),
+            Operand::IndirectY(addr) => Some(zero_page_w(addr).wrapping_add(u16::from(reg.y))),
+        };
+    }
+
+    /// Format with a label function used for addresses in the operand
+    pub fn format_with(&self, label: &dyn Fn(u16) -> Option<String>) -> String {
+        let byte = |val: u8| format!("${:02X}", val);
+        let zero_page =
+            |addr: u8| label(u16::from(addr)).unwrap_or_else(|| format!("${:02X}", addr));
+        let word = |addr: u16| label(addr).unwrap_or_else(|| format!("${:04X}", addr));
+        let operand = match self.operand {
+            Operand::Implied => return self.mnemonic.clone(),
+            Operand::Accumulator => "A".to_string(),
+            Operand::Immediate(val) => format!("#{}", byte(val)),
+            Operand::ZeroPage(addr) => zero_page(addr),
+            Operand::ZeroPageX(addr) => format!("{},X", zero_page(addr)),
+            Operand::ZeroPageY(addr) => format!("{},Y", zero_page(addr)),
+            Operand::Absolute(addr) | Operand::Relative(addr) => word(addr),
+            Operand::AbsoluteX(addr) => format!("{},X", word(addr)),
+            Operand::AbsoluteY(addr) => format!("{},Y", word(addr)),
+            Operand::Indirect(addr) => format!("({})", word(addr)),
+            Operand::IndirectX(addr) => format!("({},X)", zero_page(addr)),
+            Operand::IndirectY(addr) => format!("({}),Y", zero_page(addr)),
+        };
+        format!("{} {}", self.mnemonic, operand)
+    }
+}
+
+impl fmt::Display for DisassembledInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format_with(&|_| None))
+    }
+}
+
+/// Decode the instruction at the address
+pub fn disassemble(memory: &impl MemoryView, addr: u16) -> DisassembledInstruction {
+    let code = memory.peek(addr);
+    let inst = Instruction::from_code(code);
+    let len = 1 + u16::from(inst.mode.operand_bytes());
+    let bytes = (0..len)
+        .map(|i| memory.peek(addr.wrapping_add(i)))
+        .collect::<Vec<u8>>();
+    let byte = || bytes[1];
+    let word = || u16::from(bytes[1]) | (u16::from(bytes[2]) << 8);
+    let operand = match inst.mode {
+        AddressingMode::Implied => Operand::Implied,
+        AddressingMode::Accumulator => Operand::Accumulator,
+        AddressingMode::Immediate => Operand::Immediate(byte()),
+        AddressingMode::ZeroPage => Operand::ZeroPage(byte()),
+        AddressingMode::ZeroPageX => Operand::ZeroPageX(byte()),
+        AddressingMode::ZeroPageY => Operand::ZeroPageY(byte()),
+        AddressingMode::Absolute => Operand::Absolute(word()),
+        AddressingMode::AbsoluteX => Operand::AbsoluteX(word()),
+        AddressingMode::AbsoluteY => Operand::AbsoluteY(word()),
+        AddressingMode::Indirect => Operand::Indirect(word()),
+        AddressingMode::IndirectX => Operand::IndirectX(byte()),
+        AddressingMode::IndirectY => Operand::IndirectY(byte()),
+        AddressingMode::Relative => {
+            let next = addr.wrapping_add(len);
+            Operand::Relative(next.wrapping_add(byte() as i8 as u16))
+        }
+    };
+    DisassembledInstruction {
+        addr,
+        bytes,
+        mnemonic: match inst.opcode {
+            // named after nestest logs
+            Opcode::ISC => "ISB".to_string(),
+            opcode => format!("{:?}", opcode),
+        },
+        unofficial: inst.illegal,
+        operand,
+        effective_addr: None,
+    }
+}
+
+/// Instructions decoded from an address range, with labels on branch and jump targets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    /// decoded instructions in address order
+    pub instructions: Vec<DisassembledInstruction>,
+    /// labels defined at addresses
+    pub labels: BTreeMap<u16, String>,
+    /// `name+offset` for addresses inside a symbol. Only used in operands.
+    pub offset_labels: BTreeMap<u16, String>,
+}
+
+impl Disassembly {
+    /// Label for the address, if any
+    pub fn label(&self, addr: u16) -> Option<String> {
+        self.labels
+            .get(&addr)
+            .or_else(|| self.offset_labels.get(&addr))
+            .cloned()
+    }
+
+    /// Format an instruction using the labels
+    pub fn format_instruction(&self, inst: &DisassembledInstruction) -> String {
+        inst.format_with(&|addr| self.label(addr))
+    }
+
+    /// Name instructions and operand addresses with the symbols.
+    /// Generated `L_XXXX` labels are replaced where a symbol exists.
+    pub fn apply_symbols(&mut self, symbols: &SymbolTable, memory: &impl MemoryView) {
+        let operands = self
+            .instructions
+            .iter()
+            .filter_map(|inst| match inst.operand {
+                Operand::ZeroPage(addr)
+                | Operand::ZeroPageX(addr)
+                | Operand::ZeroPageY(addr)
+                | Operand::IndirectX(addr)
+                | Operand::IndirectY(addr) => Some(u16::from(addr)),
+                Operand::Absolute(addr)
+                | Operand::AbsoluteX(addr)
+                | Operand::AbsoluteY(addr)
+                | Operand::Indirect(addr)
+                | Operand::Relative(addr) => Some(addr),
+                _ => None,
+            });
+        let addrs = self
+            .instructions
+            .iter()
+            .map(|inst| inst.addr)
+            .chain(operands)
+            .collect::<Vec<_>>();
+        for addr in addrs {
+            match symbols.lookup(addr, memory) {
+                Some((symbol, 0)) => {
+                    self.labels.insert(addr, symbol.name.clone());
+                    self.offset_labels.remove(&addr);
+                }
+                Some((symbol, offset)) => {
+                    self.labels.remove(&addr);
+                    self.offset_labels
+                        .insert(addr, format!("{}+{}", symbol.name, offset));
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for inst in &self.instructions {
+            if let Some(label) = self.labels.get(&inst.addr) {
+                writeln!(f, "{}:", label)?;
+            }
+            let bytes = inst
+                .bytes
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(
+                f,
+                "{:04X}  {:<8}  {}",
+                inst.addr,
+                bytes,
+                self.format_instruction(inst)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode instructions linearly from `start` until `end` (inclusive)
+pub fn disassemble_range(memory: &impl MemoryView, start: u16, end: u16) -> Disassembly {
+    let mut instructions = Vec::new();
+    let mut addr = u32::from(start);
+    while addr <= u32::from(end) {
+        let inst = disassemble(memory, addr as u16);
+        addr += inst.bytes.len() as u32;
+        instructions.push(inst);
+    }
+    let labels = instructions
+        .iter()
+        .filter_map(|inst| inst.target())
+        .filter(|&target| start <= target && target <= end)
+        .map(|target| (target, format!("L_{:04X}", target)))
+        .collect();
+    Disassembly {
+        instructions,
+        labels,
+        offset_labels: BTreeMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::Symbol;
+
+    #[test]
+    fn labels_inside_symbols_are_only_used_in_operands() {
+        // LDA #$01; JMP $8002
+        let code = [0xa9, 0x01, 0x4c, 0x02, 0x80];
+        let memory = |addr: u16| code[usize::from(addr - 0x8000)];
+        let mut disassembly = disassemble_range(&memory, 0x8000, 0x8004);
+        assert_eq!(disassembly.label(0x8002), Some("L_8002".to_string()));
+
+        let mut symbols = SymbolTable::new();
+        symbols.add(Symbol {
+            name: "reset".to_string(),
+            addr: 0x8000,
+            size: 1,
+            prg_offset: None,
+        });
+        disassembly.apply_symbols(&symbols, &memory);
+        assert_eq!(
+            disassembly.to_string(),
+            "reset:\n8000  A9 01     LDA #$01\n8002  4C 02 80  JMP reset+2\n"
+        );
+    }
+}
@@ -1,15 +1,9 @@
-use crate::memory::Memory;
-use crate::ppu::Ppu;
-
 /// DMA transfer for PPU OAM
 #[derive(Default, Clone)]
 pub struct Dma {
     high: Option<u8>,
 }
 
-// TODO: the cycle is different depending on whether cpu cycle is odd or even.
-const DMA_CYCLES: u16 = 514;
-
 impl Dma {
     /// Create new instance
     pub fn new() -> Dma {
@@ -21,16 +15,8 @@ impl Dma {
         self.high = Some(value);
     }
 
-    /// Transer 256 bytes to OAM
-    pub fn transfer(&mut self, memory: &Memory, ppu: &mut Ppu) -> u16 {
-        if let Some(high) = self.high {
-            for low in 0..0x100 {
-                let addr = (u16::from(high) << 8) | low;
-                ppu.write_oam(memory.load(addr));
-            }
-            self.high = None;
-            return DMA_CYCLES;
-        }
-        0
+    /// Take high byte of address requested since the last transfer
+    pub fn take_request(&mut self) -> Option<u8> {
+        self.high.take()
     }
 }
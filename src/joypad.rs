@@ -85,6 +85,11 @@ impl JoyPad {
         result
     }
 
+    /// Value `load` would return, without shifting the buffer
+    pub fn peek(&self) -> u8 {
+        self.buffer & 0x1
+    }
+
     /// Store via memory map
     pub fn store(&mut self, value: u8) {
         self.strobe_bit = value != 0;
@@ -9,26 +9,49 @@ pub mod apu;
 pub mod bus;
 /// cartridge with two memory units
 pub mod cartridge;
+/// code/data logger for ROM disassembly
+pub mod cdl;
 /// processor
 pub mod cpu;
+/// breakpoints, watchpoints and stepping
+pub mod debugger;
+/// 6502 disassembler
+pub mod disasm;
 /// transfer whole data from ram to PPU OAM
 pub mod dma;
 /// controller
 pub mod joypad;
+/// cartridge board hardware
+pub mod mapper;
 /// volatile memory
 pub mod memory;
 /// picture processing unit
 pub mod ppu;
+/// cycle profiler for game routines
+pub mod profiler;
+/// labels loaded from debug symbol files
+pub mod symbols;
+/// runner for test ROMs reporting results at $6000
+pub mod test_rom;
+/// execution trace logger
+pub mod trace;
 
 use crate::apu::Apu;
-use crate::bus::Bus;
+use crate::bus::{Bus, BusView, OpenBus};
+use crate::cdl::CodeDataLog;
 use crate::cpu::Cpu;
 use crate::dma::Dma;
 use crate::joypad::JoyPad;
 use crate::memory::Memory;
 use crate::ppu::Ppu;
+use crate::profiler::{Profiler, RoutineKind, Step};
+use crate::trace::TraceLogger;
+use std::rc::Rc;
 
+pub use crate::bus::{AccessKind, AddressSpace, BusAccess, UnmappedAccess};
 pub use crate::cartridge::Cartridge;
+pub use crate::cartridge::CartridgeError;
+pub use crate::cpu::Registers;
 pub use crate::joypad::Key;
 pub use crate::joypad::KeyState;
 pub use crate::ppu::WINDOW_HEIGHT;
@@ -44,26 +67,34 @@ pub struct Nes {
     cpu: Cpu,
     joypad: JoyPad,
     dma: Dma,
+    open_bus: OpenBus,
+    cycles: u64,
+    trace_logger: Option<TraceLogger>,
+    profiler: Option<Profiler>,
 }
 
 impl Nes {
     /// Construct NES object with cartridge
-    pub fn with_cartridge(cartridge: Cartridge) -> Nes {
+    pub fn with_cartridge(mut cartridge: Cartridge) -> Nes {
         let mut wram = Memory::new();
         let mut apu = Apu::new();
         let mut ppu = Ppu::from_cartridge(&cartridge);
         let mut cpu = Cpu::new();
         let mut joypad = JoyPad::new();
         let mut dma = Dma::new();
+        let mut open_bus = OpenBus::new();
 
-        cpu.reset(&mut Bus::new(
-            &cartridge,
+        let mut bus = Bus::new(
+            &mut cartridge,
             &mut wram,
             &mut ppu,
             &mut apu,
             &mut joypad,
             &mut dma,
-        ));
+            &mut open_bus,
+        );
+        cpu.reset(&mut bus);
+        let cycles = bus.cycles();
 
         Nes {
             cartridge,
@@ -73,6 +104,10 @@ impl Nes {
             cpu,
             joypad,
             dma,
+            open_bus,
+            cycles,
+            trace_logger: None,
+            profiler: None,
         }
     }
 }
@@ -81,48 +116,231 @@ impl Nes {
     /// Executes CPU, PPU and APU until next frame comes.
     pub fn next_frame(&mut self, key_state: KeyState) {
         self.joypad.set_key_state(key_state);
-        loop {
-            let cycle = self.cpu.exec(&mut Bus::new(
-                &self.cartridge,
-                &mut self.wram,
-                &mut self.ppu,
-                &mut self.apu,
-                &mut self.joypad,
-                &mut self.dma,
-            )) as usize;
-            let steal = self.dma.transfer(&self.wram, &mut self.ppu) as usize;
-
-            let mut vblank_nmi = false;
-            let mut new_frame = false;
-            for _ in 0..3 * (cycle + steal) {
-                let res = self.ppu.exec();
-                vblank_nmi |= res.vblank_nmi;
-                new_frame |= res.new_frame;
-            }
-            for _ in 0..(cycle + steal) {
-                self.apu.tick();
-            }
-            if new_frame {
-                break;
-            }
-            if vblank_nmi {
-                self.cpu.nmi(&mut Bus::new(
-                    &self.cartridge,
-                    &mut self.wram,
-                    &mut self.ppu,
-                    &mut self.apu,
-                    &mut self.joypad,
-                    &mut self.dma,
-                ));
+        let frame = self.ppu.frame_count();
+        while self.ppu.frame_count() == frame {
+            self.step();
+        }
+    }
+
+    /// Executes a CPU instruction, then OAM DMA and an interrupt if they are requested.
+    pub fn step(&mut self) {
+        self.step_inner(None);
+    }
+
+    /// Executes like `step` and appends every bus access to the log
+    pub fn step_with_access_log(&mut self, log: &mut Vec<BusAccess>) {
+        self.step_inner(Some(log));
+    }
+
+    fn step_inner(&mut self, access_log: Option<&mut Vec<BusAccess>>) {
+        self.write_trace();
+        let before = self.cpu.registers();
+        let opcode = match self.profiler {
+            Some(_) => self.peek_cpu(before.pc),
+            None => 0,
+        };
+        let mut bus = Bus::new(
+            &mut self.cartridge,
+            &mut self.wram,
+            &mut self.ppu,
+            &mut self.apu,
+            &mut self.joypad,
+            &mut self.dma,
+            &mut self.open_bus,
+        );
+        bus.set_start_cycles(self.cycles);
+        if let Some(log) = access_log {
+            bus.set_access_log(log);
+        }
+        self.cpu.exec(&mut bus);
+        bus.run_dma();
+        let after = self.cpu.registers();
+        let instruction_cycles = bus.cycles();
+        let interrupt = if bus.take_vblank_nmi() {
+            self.cpu.nmi(&mut bus);
+            Some(RoutineKind::Nmi)
+        } else if bus.irq() {
+            self.cpu.irq(&mut bus);
+            Some(RoutineKind::Irq)
+        } else {
+            None
+        };
+        let interrupt_cycles = bus.cycles() - instruction_cycles;
+        self.cycles += bus.cycles();
+
+        if let Some(mut profiler) = self.profiler.take() {
+            let step = Step {
+                before,
+                after,
+                opcode,
+                cycles: instruction_cycles,
+                // IRQ is ignored while the interrupt disable flag is set
+                interrupt: interrupt
+                    .filter(|_| interrupt_cycles > 0)
+                    .map(|kind| (kind, interrupt_cycles)),
+            };
+            profiler.record(self, &step);
+            self.profiler = Some(profiler);
+        }
+    }
+
+    fn write_trace(&self) {
+        if let Some(logger) = &self.trace_logger {
+            if logger.accepts(self.cpu.registers().pc) {
+                logger.log(self);
             }
         }
     }
 
+    /// Write executed instructions into the logger
+    pub fn set_trace_logger(&mut self, logger: TraceLogger) {
+        self.trace_logger = Some(logger);
+    }
+
+    /// Stop tracing. Returns the logger which was set.
+    pub fn clear_trace_logger(&mut self) -> Option<TraceLogger> {
+        let logger = self.trace_logger.take();
+        if let Some(logger) = &logger {
+            logger.flush();
+        }
+        logger
+    }
+
+    /// Attribute cycles to routines while running
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    /// Profiler which is attached
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Stop profiling. Returns the profiler which was set.
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    /// Press reset button
+    pub fn reset(&mut self) {
+        // reset silences APU
+        self.apu.store(0x15, 0);
+        let mut bus = Bus::new(
+            &mut self.cartridge,
+            &mut self.wram,
+            &mut self.ppu,
+            &mut self.apu,
+            &mut self.joypad,
+            &mut self.dma,
+            &mut self.open_bus,
+        );
+        bus.set_start_cycles(self.cycles);
+        self.cpu.soft_reset(&mut bus);
+        self.cycles += bus.cycles();
+    }
+
+    /// True after the CPU locked up by KIL instruction
+    pub fn is_cpu_halted(&self) -> bool {
+        self.cpu.is_halted()
+    }
+
+    /// Number of CPU clocks since power on
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Current CPU registers
+    pub fn cpu_registers(&self) -> Registers {
+        self.cpu.registers()
+    }
+
+    /// Move CPU program counter
+    pub fn set_pc(&mut self, pc: u16) {
+        self.cpu.set_pc(pc);
+    }
+
+    /// Current PPU position as (scanline, dot)
+    pub fn ppu_position(&self) -> (u16, u16) {
+        (self.ppu.scanline(), self.ppu.dot())
+    }
+
+    /// Number of frames started since power on
+    pub fn frame_count(&self) -> u64 {
+        self.ppu.frame_count()
+    }
+
+    /// Value a cpu load from the address would return, without side effects.
+    /// Tools can inspect memory and registers without disturbing the emulation.
+    pub fn peek_cpu(&self, addr: u16) -> u8 {
+        BusView::new(
+            &self.cartridge,
+            &self.wram,
+            &self.ppu,
+            &self.apu,
+            &self.joypad,
+            &self.open_bus,
+        )
+        .peek(addr)
+    }
+
+    /// Byte in ppu address space ($0000-$3FFF) without side effects
+    pub fn peek_ppu(&self, addr: u16) -> u8 {
+        self.ppu.peek_vram(addr, &self.cartridge)
+    }
+
+    /// Byte in OAM
+    pub fn peek_oam(&self, index: u8) -> u8 {
+        self.ppu.peek_oam(index)
+    }
+
+    /// Inserted cartridge
+    pub fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
     /// Return frame buffer in the last frame
     pub fn get_frame_buffer(&self) -> &[u8] {
         &self.ppu.get_buffer()
     }
 
+    /// Report loads and stores to unmapped cpu addresses to the callback.
+    /// Without strict mode, unmapped loads return open bus and stores are ignored.
+    pub fn set_strict_mode<F: Fn(UnmappedAccess) + 'static>(&mut self, callback: F) {
+        self.open_bus.set_strict_mode(Rc::new(callback));
+    }
+
+    /// Stop reporting unmapped accesses
+    pub fn clear_strict_mode(&mut self) {
+        self.open_bus.clear_strict_mode();
+    }
+
+    /// Battery-backed PRG RAM to write into a save file.
+    /// None if the cartridge has no battery.
+    pub fn save_data(&self) -> Option<&[u8]> {
+        self.cartridge.save_data()
+    }
+
+    /// Restore battery-backed PRG RAM from a save file
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        self.cartridge.load_save_data(data)
+    }
+
+    /// Record which ROM bytes are executed, read as data and rendered.
+    /// Export the result with `CodeDataLog::write_fceux`.
+    pub fn start_code_data_log(&mut self) {
+        self.cartridge.start_code_data_log();
+    }
+
+    /// Code/Data Log recorded since `start_code_data_log`
+    pub fn code_data_log(&self) -> Option<&CodeDataLog> {
+        self.cartridge.code_data_log()
+    }
+
+    /// Stop recording and return the Code/Data Log
+    pub fn take_code_data_log(&mut self) -> Option<CodeDataLog> {
+        self.cartridge.take_code_data_log()
+    }
+
     /// Consume audio buffer in APU
     pub fn consume_audio_buffer(&mut self) -> Vec<(f32, f32)> {
         self.apu.consume_buffer().collect::<Vec<_>>()
@@ -7,6 +7,7 @@ use std::env;
 use std::error::Error;
 use std::fs::File;
 use std::io::prelude::*;
+use std::path::{Path, PathBuf};
 
 use nes::Cartridge;
 use nes::Key;
@@ -36,7 +37,7 @@ fn joypad_key(keycode: Keycode) -> Option<Key> {
     }
 }
 
-fn read_cartridge(path: &str) -> Result<Cartridge, Box<Error>> {
+fn read_cartridge(path: &str) -> Result<Cartridge, Box<dyn Error>> {
     let mut f = File::open(path)?;
     let mut buffer = Vec::new();
     f.read_to_end(&mut buffer)?;
@@ -44,7 +45,27 @@ fn read_cartridge(path: &str) -> Result<Cartridge, Box<Error>> {
     Ok(cartridge)
 }
 
-fn create_canvas(sdl: &Sdl) -> Result<WindowCanvas, Box<Error>> {
+fn save_file_path(rom_path: &str) -> PathBuf {
+    Path::new(rom_path).with_extension("sav")
+}
+
+fn read_save_file(path: &Path) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut f = File::open(path)?;
+    let mut buffer = Vec::new();
+    f.read_to_end(&mut buffer)?;
+    Ok(Some(buffer))
+}
+
+fn write_save_file(path: &Path, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut f = File::create(path)?;
+    f.write_all(data)?;
+    Ok(())
+}
+
+fn create_canvas(sdl: &Sdl) -> Result<WindowCanvas, Box<dyn Error>> {
     let video_subsystem = sdl.video()?;
 
     let window = video_subsystem
@@ -72,7 +93,7 @@ fn create_canvas(sdl: &Sdl) -> Result<WindowCanvas, Box<Error>> {
     Ok(canvas)
 }
 
-fn main() -> Result<(), Box<Error>> {
+fn main() -> Result<(), Box<dyn Error>> {
     env_logger::Builder::new()
         .filter(None, log::LevelFilter::Info)
         .init();
@@ -83,6 +104,14 @@ fn main() -> Result<(), Box<Error>> {
     let cartridge = read_cartridge(&path)?;
     let mut nes = Nes::with_cartridge(cartridge);
 
+    let save_path = save_file_path(&path);
+    if nes.save_data().is_some() {
+        if let Some(data) = read_save_file(&save_path)? {
+            log::info!("Loading save data from {}", save_path.display());
+            nes.load_save_data(&data);
+        }
+    }
+
     let sdl_context = sdl2::init()?;
     let mut canvas = create_canvas(&sdl_context)?;
 
@@ -153,5 +182,10 @@ fn main() -> Result<(), Box<Error>> {
         canvas.present();
     }
 
+    if let Some(data) = nes.save_data() {
+        log::info!("Writing save data to {}", save_path.display());
+        write_save_file(&save_path, data)?;
+    }
+
     Ok(())
 }
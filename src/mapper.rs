@@ -0,0 +1,150 @@
+mod axrom;
+mod chr;
+mod cnrom;
+mod gxrom;
+mod mmc1;
+mod mmc3;
+mod nrom;
+mod prg_ram;
+mod uxrom;
+
+use self::axrom::Axrom;
+use self::chr::ChrMemory;
+use self::cnrom::Cnrom;
+use self::gxrom::Gxrom;
+use self::mmc1::Mmc1;
+use self::mmc3::Mmc3;
+use self::nrom::Nrom;
+use self::prg_ram::PrgRam;
+use self::uxrom::Uxrom;
+use crate::cartridge::{CartridgeError, CartridgeHeader};
+
+/// Nametable arrangement controlled by the cartridge board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    /// $2000 equals $2800 and $2400 equals $2C00
+    Vertical,
+    /// $2000 equals $2400 and $2800 equals $2C00
+    Horizontal,
+    /// All nametables refer to the first VRAM page
+    SingleScreenLower,
+    /// All nametables refer to the second VRAM page
+    SingleScreenUpper,
+    /// Each nametable has its own page, with 2KB extra VRAM on the board
+    FourScreen,
+}
+
+/// Cartridge board hardware which maps ROM/RAM chips into CPU and PPU address space
+pub trait Mapper {
+    /// Load 1 byte from CPU address ($4020-$FFFF).
+    /// Returns None if nothing is mapped at the address.
+    fn cpu_load(&self, addr: u16) -> Option<u8>;
+
+    /// Offset in PRG ROM currently mapped at CPU address.
+    /// Returns None if the address is not mapped to PRG ROM.
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize>;
+
+    /// Store 1 byte into CPU address ($4020-$FFFF).
+    /// `cpu_cycle` counts CPU clocks since power on.
+    /// Returns false if nothing on the board received the write.
+    fn cpu_store(&mut self, addr: u16, val: u8, cpu_cycle: u64) -> bool;
+
+    /// Load 1 byte from PPU address ($0000-$1FFF)
+    fn ppu_load(&self, addr: u16) -> u8;
+
+    /// Offset in CHR ROM currently mapped at PPU address ($0000-$1FFF).
+    /// Returns None if the address is mapped to CHR RAM.
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize>;
+
+    /// Store 1 byte into PPU address ($0000-$1FFF)
+    fn ppu_store(&mut self, addr: u16, val: u8);
+
+    /// Current nametable mirroring
+    fn mirroring(&self) -> Mirroring;
+
+    /// Observe an address the PPU puts on its bus when A12 changes.
+    /// `ppu_cycle` counts PPU dots since power on.
+    /// Boards like MMC3 clock their scanline counter from it.
+    fn notify_ppu_address(&mut self, _addr: u16, _ppu_cycle: u64) {}
+
+    /// True while the board asserts the CPU IRQ line
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// PRG RAM on the board. Empty if the board has none.
+    fn prg_ram(&self) -> &[u8] {
+        &[]
+    }
+
+    /// Mutable PRG RAM on the board
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+
+    /// Clone into a new boxed mapper
+    fn box_clone(&self) -> Box<dyn Mapper>;
+}
+
+impl Clone for Box<dyn Mapper> {
+    fn clone(&self) -> Box<dyn Mapper> {
+        self.box_clone()
+    }
+}
+
+// Value latched by a discrete board register. With bus conflicts, the ROM drives
+// the data bus at the same time and the written value is ANDed with the ROM byte.
+fn bus_conflict(rom: &[u8], offset: usize, val: u8, enabled: bool) -> u8 {
+    if enabled {
+        val & rom[offset]
+    } else {
+        val
+    }
+}
+
+// Boards without CHR ROM have 8KB CHR RAM unless NES 2.0 header says otherwise.
+const DEFAULT_CHR_RAM_SIZE: usize = 0x2000;
+
+/// Create mapper from the mapper number in the header
+pub fn create(
+    header: &CartridgeHeader,
+    prg_rom: Vec<u8>,
+    chr_rom: Vec<u8>,
+) -> Result<Box<dyn Mapper>, CartridgeError> {
+    let mirroring = header.mirroring;
+    let chr_ram_size = match header.chr_ram_size + header.chr_nvram_size {
+        0 if chr_rom.is_empty() => DEFAULT_CHR_RAM_SIZE,
+        size => size,
+    };
+    let chr = ChrMemory::new(chr_rom, chr_ram_size);
+    let prg_ram = PrgRam::new(header.prg_ram_size + header.prg_nvram_size);
+    // Discrete boards use submapper 1 for "no bus conflicts" and 2 for "bus conflicts".
+    let bus_conflicts = |default| match header.submapper {
+        1 => false,
+        2 => true,
+        _ => default,
+    };
+    match header.mapper {
+        0 => Ok(Box::new(Nrom::new(prg_rom, chr, prg_ram, mirroring))),
+        1 => Ok(Box::new(Mmc1::new(prg_rom, chr, prg_ram))),
+        2 => Ok(Box::new(Uxrom::new(
+            prg_rom,
+            chr,
+            mirroring,
+            bus_conflicts(true),
+        ))),
+        3 => Ok(Box::new(Cnrom::new(
+            prg_rom,
+            chr,
+            mirroring,
+            bus_conflicts(true),
+        ))),
+        4 => Ok(Box::new(Mmc3::new(prg_rom, chr, prg_ram, mirroring))),
+        7 => Ok(Box::new(Axrom::new(prg_rom, chr, bus_conflicts(false)))),
+        66 => Ok(Box::new(Gxrom::new(prg_rom, chr, mirroring))),
+        mapper => Err(CartridgeError::UnsupportedMapper {
+            mapper,
+            submapper: header.submapper,
+        }),
+    }
+}
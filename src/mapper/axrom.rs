@@ -0,0 +1,84 @@
+use super::chr::ChrMemory;
+use super::{bus_conflict, Mapper, Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x8000;
+
+/// Mapper 7. Switchable 32KB PRG bank and single-screen mirroring.
+/// See https://wiki.nesdev.com/w/index.php/AxROM
+#[derive(Clone)]
+pub struct Axrom {
+    prg_rom: Vec<u8>,
+    chr: ChrMemory,
+    bus_conflicts: bool,
+    bank: u8,
+}
+
+impl Axrom {
+    pub fn new(prg_rom: Vec<u8>, chr: ChrMemory, bus_conflicts: bool) -> Axrom {
+        Axrom {
+            prg_rom,
+            chr,
+            bus_conflicts,
+            bank: 0,
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let banks = (self.prg_rom.len() / PRG_BANK_SIZE).max(1);
+        let bank = usize::from(self.bank & 0x07) % banks;
+        (bank * PRG_BANK_SIZE + usize::from(addr - 0x8000)) % self.prg_rom.len()
+    }
+}
+
+impl Mapper for Axrom {
+    fn cpu_load(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xffff => Some(self.prg_rom[self.prg_offset(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_store(&mut self, addr: u16, val: u8, _cpu_cycle: u64) -> bool {
+        if addr < 0x8000 {
+            return false;
+        }
+        self.bank = bus_conflict(
+            &self.prg_rom,
+            self.prg_offset(addr),
+            val,
+            self.bus_conflicts,
+        );
+        true
+    }
+
+    fn ppu_load(&self, addr: u16) -> u8 {
+        self.chr.load(addr as usize)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chr.rom_offset(addr as usize)
+    }
+
+    fn ppu_store(&mut self, addr: u16, val: u8) {
+        self.chr.store(addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.bank & 0x10 == 0 {
+            Mirroring::SingleScreenLower
+        } else {
+            Mirroring::SingleScreenUpper
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xffff => Some(self.prg_offset(addr)),
+            _ => None,
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
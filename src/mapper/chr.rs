@@ -0,0 +1,44 @@
+/// CHR memory on a board. CHR ROM followed by CHR RAM,
+/// so either of them can be empty and boards can mix both.
+#[derive(Clone)]
+pub struct ChrMemory {
+    data: Vec<u8>,
+    ram_start: usize,
+}
+
+impl ChrMemory {
+    pub fn new(chr_rom: Vec<u8>, ram_size: usize) -> ChrMemory {
+        let ram_start = chr_rom.len();
+        let mut data = chr_rom;
+        data.resize(ram_start + ram_size, 0);
+        ChrMemory { data, ram_start }
+    }
+
+    /// Total size of ROM and RAM in bytes
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Load from offset. Offset wraps around total size.
+    pub fn load(&self, offset: usize) -> u8 {
+        self.data[offset % self.data.len()]
+    }
+
+    /// Offset in CHR ROM. None if the offset wraps into CHR RAM.
+    pub fn rom_offset(&self, offset: usize) -> Option<usize> {
+        let offset = offset % self.data.len();
+        if offset < self.ram_start {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+
+    /// Store into offset. Writes to ROM area are ignored.
+    pub fn store(&mut self, offset: usize, val: u8) {
+        let offset = offset % self.data.len();
+        if offset >= self.ram_start {
+            self.data[offset] = val;
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use super::chr::ChrMemory;
+use super::{bus_conflict, Mapper, Mirroring};
+
+const CHR_BANK_SIZE: usize = 0x2000;
+
+/// Mapper 3. Fixed PRG and switchable 8KB CHR bank.
+/// See https://wiki.nesdev.com/w/index.php/CNROM
+#[derive(Clone)]
+pub struct Cnrom {
+    prg_rom: Vec<u8>,
+    chr: ChrMemory,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+    chr_bank: u8,
+}
+
+impl Cnrom {
+    pub fn new(
+        prg_rom: Vec<u8>,
+        chr: ChrMemory,
+        mirroring: Mirroring,
+        bus_conflicts: bool,
+    ) -> Cnrom {
+        Cnrom {
+            prg_rom,
+            chr,
+            mirroring,
+            bus_conflicts,
+            chr_bank: 0,
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        usize::from(addr - 0x8000) % self.prg_rom.len()
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let banks = self.chr.len() / CHR_BANK_SIZE;
+        let bank = usize::from(self.chr_bank) % banks;
+        bank * CHR_BANK_SIZE + addr as usize
+    }
+}
+
+impl Mapper for Cnrom {
+    fn cpu_load(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xffff => Some(self.prg_rom[self.prg_offset(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_store(&mut self, addr: u16, val: u8, _cpu_cycle: u64) -> bool {
+        if addr < 0x8000 {
+            return false;
+        }
+        self.chr_bank = bus_conflict(
+            &self.prg_rom,
+            self.prg_offset(addr),
+            val,
+            self.bus_conflicts,
+        );
+        true
+    }
+
+    fn ppu_load(&self, addr: u16) -> u8 {
+        self.chr.load(self.chr_offset(addr))
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chr.rom_offset(self.chr_offset(addr))
+    }
+
+    fn ppu_store(&mut self, addr: u16, val: u8) {
+        let offset = self.chr_offset(addr);
+        self.chr.store(offset, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xffff => Some(self.prg_offset(addr)),
+            _ => None,
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
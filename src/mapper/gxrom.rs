@@ -0,0 +1,84 @@
+use super::chr::ChrMemory;
+use super::{bus_conflict, Mapper, Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x8000;
+const CHR_BANK_SIZE: usize = 0x2000;
+
+/// Mapper 66. Switchable 32KB PRG bank and 8KB CHR bank.
+/// See https://wiki.nesdev.com/w/index.php/GxROM
+#[derive(Clone)]
+pub struct Gxrom {
+    prg_rom: Vec<u8>,
+    chr: ChrMemory,
+    mirroring: Mirroring,
+    bank: u8,
+}
+
+impl Gxrom {
+    pub fn new(prg_rom: Vec<u8>, chr: ChrMemory, mirroring: Mirroring) -> Gxrom {
+        Gxrom {
+            prg_rom,
+            chr,
+            mirroring,
+            bank: 0,
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let banks = (self.prg_rom.len() / PRG_BANK_SIZE).max(1);
+        let bank = usize::from((self.bank >> 4) & 0x03) % banks;
+        (bank * PRG_BANK_SIZE + usize::from(addr - 0x8000)) % self.prg_rom.len()
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let banks = self.chr.len() / CHR_BANK_SIZE;
+        let bank = usize::from(self.bank & 0x03) % banks;
+        bank * CHR_BANK_SIZE + addr as usize
+    }
+}
+
+impl Mapper for Gxrom {
+    fn cpu_load(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xffff => Some(self.prg_rom[self.prg_offset(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_store(&mut self, addr: u16, val: u8, _cpu_cycle: u64) -> bool {
+        if addr < 0x8000 {
+            return false;
+        }
+        // The board always has bus conflicts.
+        self.bank = bus_conflict(&self.prg_rom, self.prg_offset(addr), val, true);
+        true
+    }
+
+    fn ppu_load(&self, addr: u16) -> u8 {
+        self.chr.load(self.chr_offset(addr))
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chr.rom_offset(self.chr_offset(addr))
+    }
+
+    fn ppu_store(&mut self, addr: u16, val: u8) {
+        let offset = self.chr_offset(addr);
+        self.chr.store(offset, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xffff => Some(self.prg_offset(addr)),
+            _ => None,
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,212 @@
+use super::chr::ChrMemory;
+use super::prg_ram::PrgRam;
+use super::{Mapper, Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x1000;
+
+// SUROM and SXROM boards select the upper 256KB of PRG with CHR bank bit 4.
+const PRG_OUTER_BANK_SIZE: usize = 0x40000;
+
+/// Mapper 1. Registers are written serially through a 5-bit shift register.
+/// See https://wiki.nesdev.com/w/index.php/MMC1
+#[derive(Clone)]
+pub struct Mmc1 {
+    prg_rom: Vec<u8>,
+    chr: ChrMemory,
+    prg_ram: PrgRam,
+    shift: u8,
+    shift_count: u8,
+    control: u8,
+    chr_bank0: u8,
+    chr_bank1: u8,
+    prg_bank: u8,
+    // cpu cycle of the last write to the shift register
+    last_write_cycle: Option<u64>,
+}
+
+impl Mmc1 {
+    pub fn new(prg_rom: Vec<u8>, chr: ChrMemory, prg_ram: PrgRam) -> Mmc1 {
+        Mmc1 {
+            prg_rom,
+            chr,
+            prg_ram,
+            shift: 0,
+            shift_count: 0,
+            // PRG mode 3 at power-up so that the last bank holds the reset vector
+            control: 0x0c,
+            chr_bank0: 0,
+            chr_bank1: 0,
+            prg_bank: 0,
+            last_write_cycle: None,
+        }
+    }
+
+    fn write_shift_register(&mut self, addr: u16, val: u8) {
+        if val & 0x80 != 0 {
+            self.shift = 0;
+            self.shift_count = 0;
+            self.control |= 0x0c;
+            return;
+        }
+        self.shift |= (val & 0x01) << self.shift_count;
+        self.shift_count += 1;
+        if self.shift_count == 5 {
+            let value = self.shift;
+            match addr {
+                0x8000..=0x9fff => self.control = value,
+                0xa000..=0xbfff => self.chr_bank0 = value,
+                0xc000..=0xdfff => self.chr_bank1 = value,
+                0xe000..=0xffff => self.prg_bank = value,
+                _ => unreachable!(),
+            }
+            self.shift = 0;
+            self.shift_count = 0;
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0x10 == 0
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let banks = (self.prg_rom.len() / PRG_BANK_SIZE).min(PRG_OUTER_BANK_SIZE / PRG_BANK_SIZE);
+        let outer = if self.prg_rom.len() > PRG_OUTER_BANK_SIZE {
+            usize::from(self.chr_bank0 & 0x10) >> 4
+        } else {
+            0
+        };
+        let bank = usize::from(self.prg_bank & 0x0f);
+        let slot = usize::from(addr - 0x8000) / PRG_BANK_SIZE;
+        let selected = match (self.control >> 2) & 0x03 {
+            0 | 1 => (bank & !1) + slot,
+            2 if slot == 0 => 0,
+            2 => bank,
+            3 if slot == 0 => bank,
+            3 => banks - 1,
+            _ => unreachable!(),
+        };
+        let offset = (selected % banks) * PRG_BANK_SIZE + usize::from(addr) % PRG_BANK_SIZE;
+        outer * PRG_OUTER_BANK_SIZE + offset
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let banks = self.chr.len() / CHR_BANK_SIZE;
+        let slot = usize::from(addr) / CHR_BANK_SIZE;
+        let selected = if self.control & 0x10 == 0 {
+            usize::from(self.chr_bank0 & !1) + slot
+        } else if slot == 0 {
+            usize::from(self.chr_bank0)
+        } else {
+            usize::from(self.chr_bank1)
+        };
+        (selected % banks) * CHR_BANK_SIZE + usize::from(addr) % CHR_BANK_SIZE
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cpu_load(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7fff if self.prg_ram_enabled() => self.prg_ram.load(addr),
+            0x8000..=0xffff => Some(self.prg_rom[self.prg_offset(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_store(&mut self, addr: u16, val: u8, cpu_cycle: u64) -> bool {
+        match addr {
+            0x6000..=0x7fff if self.prg_ram_enabled() => self.prg_ram.store(addr, val),
+            0x8000..=0xffff => {
+                // The shift register ignores a write on the cycle right after another,
+                // so only the first write of read-modify-write instructions counts.
+                let consecutive = self.last_write_cycle == Some(cpu_cycle.wrapping_sub(1));
+                self.last_write_cycle = Some(cpu_cycle);
+                if !consecutive {
+                    self.write_shift_register(addr, val);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn ppu_load(&self, addr: u16) -> u8 {
+        self.chr.load(self.chr_offset(addr))
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chr.rom_offset(self.chr_offset(addr))
+    }
+
+    fn ppu_store(&mut self, addr: u16, val: u8) {
+        let offset = self.chr_offset(addr);
+        self.chr.store(offset, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            3 => Mirroring::Horizontal,
+            _ => unreachable!(),
+        }
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        self.prg_ram.as_slice()
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        self.prg_ram.as_mut_slice()
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xffff => Some(self.prg_offset(addr)),
+            _ => None,
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cartridge, Nes};
+
+    #[test]
+    fn rmw_instruction_writes_shift_register_once() {
+        let mut rom = vec![
+            0x4e, 0x45, 0x53, 0x1a, 2, 1, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut prg = vec![0xea; 0x8000];
+        // bank 0 has $FF at $8000. INC $8000 resets with $FF and writes $00 on the next cycle.
+        prg[0] = 0xff;
+        // then select PRG bank 1 at $8000 with 5 serial writes of 1, 0, 0, 0, 0
+        let code = [
+            0xee, 0x00, 0x80, // INC $8000
+            0xa9, 0x01, // LDA #$01
+            0x8d, 0x00, 0xe0, // STA $E000
+            0xa9, 0x00, // LDA #$00
+            0x8d, 0x00, 0xe0, // STA $E000
+            0x8d, 0x00, 0xe0, // STA $E000
+            0x8d, 0x00, 0xe0, // STA $E000
+            0x8d, 0x00, 0xe0, // STA $E000
+        ];
+        prg[0x4000..0x4000 + code.len()].copy_from_slice(&code);
+        prg[0x7ffc] = 0x00;
+        prg[0x7ffd] = 0xc0;
+        rom.extend(prg);
+        rom.extend(vec![0; 0x2000]);
+        let mut nes = Nes::with_cartridge(Cartridge::parse_file(&rom).unwrap());
+
+        for _ in 0..8 {
+            nes.step();
+        }
+        // bank 1 starts with INC
+        assert_eq!(nes.peek_cpu(0x8000), 0xee);
+    }
+}
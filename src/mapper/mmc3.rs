@@ -0,0 +1,229 @@
+use super::chr::ChrMemory;
+use super::prg_ram::PrgRam;
+use super::{Mapper, Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x0400;
+// 3 cpu cycles
+const A12_LOW_CYCLES: u64 = 9;
+
+/// Mapper 4. 8KB PRG and 1KB CHR banks with a scanline counter
+/// clocked by rising edges of PPU A12.
+/// See https://wiki.nesdev.com/w/index.php/MMC3
+#[derive(Clone)]
+pub struct Mmc3 {
+    prg_rom: Vec<u8>,
+    chr: ChrMemory,
+    prg_ram: PrgRam,
+    bank_select: u8,
+    banks: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram_enabled: bool,
+    prg_ram_write_protected: bool,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    last_a12: bool,
+    // ppu cycle when A12 went low
+    a12_low_since: u64,
+}
+
+impl Mmc3 {
+    pub fn new(prg_rom: Vec<u8>, chr: ChrMemory, prg_ram: PrgRam, mirroring: Mirroring) -> Mmc3 {
+        Mmc3 {
+            prg_rom,
+            chr,
+            prg_ram,
+            bank_select: 0,
+            banks: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring,
+            prg_ram_enabled: true,
+            prg_ram_write_protected: false,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            last_a12: false,
+            a12_low_since: 0,
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let banks = self.prg_rom.len() / PRG_BANK_SIZE;
+        let slot = usize::from(addr - 0x8000) / PRG_BANK_SIZE;
+        let swapped = self.bank_select & 0x40 != 0;
+        let selected = match (slot, swapped) {
+            (0, false) | (2, true) => usize::from(self.banks[6]),
+            (1, _) => usize::from(self.banks[7]),
+            (0, true) | (2, false) => banks - 2,
+            (3, _) => banks - 1,
+            _ => unreachable!(),
+        };
+        (selected % banks) * PRG_BANK_SIZE + usize::from(addr) % PRG_BANK_SIZE
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let banks = self.chr.len() / CHR_BANK_SIZE;
+        // With CHR A12 inversion, the 2KB banks move to $1000-$1FFF.
+        let addr = if self.bank_select & 0x80 != 0 {
+            addr ^ 0x1000
+        } else {
+            addr
+        };
+        let slot = usize::from(addr) / CHR_BANK_SIZE;
+        let selected = match slot {
+            0 | 1 => usize::from(self.banks[0] & !1) + slot,
+            2 | 3 => usize::from(self.banks[1] & !1) + slot - 2,
+            4..=7 => usize::from(self.banks[slot - 2]),
+            _ => unreachable!(),
+        };
+        (selected % banks) * CHR_BANK_SIZE + usize::from(addr) % CHR_BANK_SIZE
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn cpu_load(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7fff if self.prg_ram_enabled => self.prg_ram.load(addr),
+            0x8000..=0xffff => Some(self.prg_rom[self.prg_offset(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_store(&mut self, addr: u16, val: u8, _cpu_cycle: u64) -> bool {
+        let even = addr & 0x01 == 0;
+        match addr {
+            0x6000..=0x7fff if self.prg_ram_enabled && !self.prg_ram_write_protected => {
+                return self.prg_ram.store(addr, val);
+            }
+            0x8000..=0x9fff if even => self.bank_select = val,
+            0x8000..=0x9fff => self.banks[usize::from(self.bank_select & 0x07)] = val,
+            0xa000..=0xbfff if even => {
+                self.mirroring = if val & 0x01 == 0 {
+                    Mirroring::Vertical
+                } else {
+                    Mirroring::Horizontal
+                };
+            }
+            0xa000..=0xbfff => {
+                self.prg_ram_enabled = val & 0x80 != 0;
+                self.prg_ram_write_protected = val & 0x40 != 0;
+            }
+            0xc000..=0xdfff if even => self.irq_latch = val,
+            0xc000..=0xdfff => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            0xe000..=0xffff if even => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            0xe000..=0xffff => self.irq_enabled = true,
+            _ => return false,
+        }
+        true
+    }
+
+    fn ppu_load(&self, addr: u16) -> u8 {
+        self.chr.load(self.chr_offset(addr))
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chr.rom_offset(self.chr_offset(addr))
+    }
+
+    fn ppu_store(&mut self, addr: u16, val: u8) {
+        let offset = self.chr_offset(addr);
+        self.chr.store(offset, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn notify_ppu_address(&mut self, addr: u16, ppu_cycle: u64) {
+        let a12 = addr & 0x1000 != 0;
+        // A rise counts only after A12 stayed low for a while,
+        // which filters the short toggles between the sprite fetches of a line.
+        if a12 && !self.last_a12 && ppu_cycle - self.a12_low_since >= A12_LOW_CYCLES {
+            self.clock_irq_counter();
+        }
+        if !a12 && self.last_a12 {
+            self.a12_low_since = ppu_cycle;
+        }
+        self.last_a12 = a12;
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        self.prg_ram.as_slice()
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        self.prg_ram.as_mut_slice()
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xffff => Some(self.prg_offset(addr)),
+            _ => None,
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mmc3_with_irq_latch(latch: u8) -> Mmc3 {
+        let mut mmc3 = Mmc3::new(
+            vec![0; 0x8000],
+            ChrMemory::new(vec![0; 0x2000], 0),
+            PrgRam::new(0x2000),
+            Mirroring::Vertical,
+        );
+        mmc3.cpu_store(0xc000, latch, 0);
+        mmc3.cpu_store(0xc001, 0, 2);
+        mmc3.cpu_store(0xe001, 0, 4);
+        mmc3
+    }
+
+    #[test]
+    fn short_a12_low_periods_are_filtered() {
+        let mut mmc3 = mmc3_with_irq_latch(1);
+        // sprite fetches: nametable at $2xxx, then pattern at $1xxx every 8 dots
+        mmc3.notify_ppu_address(0x1000, 100);
+        for slot in 1..8 {
+            mmc3.notify_ppu_address(0x2000, 100 + slot * 8 - 4);
+            mmc3.notify_ppu_address(0x1000, 100 + slot * 8);
+        }
+        // reloaded with 1 by the first rise only
+        assert!(!mmc3.irq());
+
+        mmc3.notify_ppu_address(0x0000, 200);
+        mmc3.notify_ppu_address(0x1000, 200 + A12_LOW_CYCLES);
+        assert!(mmc3.irq());
+    }
+}
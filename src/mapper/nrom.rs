@@ -0,0 +1,77 @@
+use super::chr::ChrMemory;
+use super::prg_ram::PrgRam;
+use super::{Mapper, Mirroring};
+
+/// Mapper 0. No bank switching. 16KB (NROM-128) or 32KB (NROM-256) PRG ROM.
+#[derive(Clone)]
+pub struct Nrom {
+    prg_rom: Vec<u8>,
+    chr: ChrMemory,
+    prg_ram: PrgRam,
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub fn new(prg_rom: Vec<u8>, chr: ChrMemory, prg_ram: PrgRam, mirroring: Mirroring) -> Nrom {
+        Nrom {
+            prg_rom,
+            chr,
+            prg_ram,
+            mirroring,
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_load(&self, addr: u16) -> Option<u8> {
+        match addr {
+            // NROM-128 mirrors 16KB PRG ROM into $C000-$FFFF
+            0x6000..=0x7fff => self.prg_ram.load(addr),
+            0x8000..=0xffff => Some(self.prg_rom[(addr - 0x8000) as usize % self.prg_rom.len()]),
+            _ => None,
+        }
+    }
+
+    fn cpu_store(&mut self, addr: u16, val: u8, _cpu_cycle: u64) -> bool {
+        match addr {
+            0x6000..=0x7fff => self.prg_ram.store(addr, val),
+            // ROM only
+            _ => false,
+        }
+    }
+
+    fn ppu_load(&self, addr: u16) -> u8 {
+        self.chr.load(addr as usize)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chr.rom_offset(addr as usize)
+    }
+
+    fn ppu_store(&mut self, addr: u16, val: u8) {
+        self.chr.store(addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        self.prg_ram.as_slice()
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        self.prg_ram.as_mut_slice()
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xffff => Some((addr - 0x8000) as usize % self.prg_rom.len()),
+            _ => None,
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
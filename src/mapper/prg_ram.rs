@@ -0,0 +1,39 @@
+/// PRG RAM window at $6000-$7FFF. Smaller chips are mirrored through the window.
+#[derive(Clone)]
+pub struct PrgRam {
+    data: Vec<u8>,
+}
+
+impl PrgRam {
+    pub fn new(size: usize) -> PrgRam {
+        PrgRam {
+            data: vec![0; size],
+        }
+    }
+
+    /// Load from cpu address. None if the board has no PRG RAM.
+    pub fn load(&self, addr: u16) -> Option<u8> {
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(self.data[usize::from(addr - 0x6000) % self.data.len()])
+    }
+
+    /// Store into cpu address. Returns false if the board has no PRG RAM.
+    pub fn store(&mut self, addr: u16, val: u8) -> bool {
+        if self.data.is_empty() {
+            return false;
+        }
+        let len = self.data.len();
+        self.data[usize::from(addr - 0x6000) % len] = val;
+        true
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
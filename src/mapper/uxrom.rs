@@ -0,0 +1,90 @@
+use super::chr::ChrMemory;
+use super::{bus_conflict, Mapper, Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+
+/// Mapper 2. Switchable 16KB bank at $8000 and the last bank fixed at $C000.
+/// See https://wiki.nesdev.com/w/index.php/UxROM
+#[derive(Clone)]
+pub struct Uxrom {
+    prg_rom: Vec<u8>,
+    chr: ChrMemory,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+    prg_bank: u8,
+}
+
+impl Uxrom {
+    pub fn new(
+        prg_rom: Vec<u8>,
+        chr: ChrMemory,
+        mirroring: Mirroring,
+        bus_conflicts: bool,
+    ) -> Uxrom {
+        Uxrom {
+            prg_rom,
+            chr,
+            mirroring,
+            bus_conflicts,
+            prg_bank: 0,
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let banks = self.prg_rom.len() / PRG_BANK_SIZE;
+        let selected = match addr {
+            0x8000..=0xbfff => usize::from(self.prg_bank),
+            _ => banks - 1,
+        };
+        (selected % banks) * PRG_BANK_SIZE + usize::from(addr) % PRG_BANK_SIZE
+    }
+}
+
+impl Mapper for Uxrom {
+    fn cpu_load(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xffff => Some(self.prg_rom[self.prg_offset(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_store(&mut self, addr: u16, val: u8, _cpu_cycle: u64) -> bool {
+        if addr < 0x8000 {
+            return false;
+        }
+        self.prg_bank = bus_conflict(
+            &self.prg_rom,
+            self.prg_offset(addr),
+            val,
+            self.bus_conflicts,
+        );
+        true
+    }
+
+    fn ppu_load(&self, addr: u16) -> u8 {
+        self.chr.load(addr as usize)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chr.rom_offset(addr as usize)
+    }
+
+    fn ppu_store(&mut self, addr: u16, val: u8) {
+        self.chr.store(addr as usize, val);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xffff => Some(self.prg_offset(addr)),
+            _ => None,
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
@@ -2,15 +2,19 @@ mod background;
 mod palette;
 mod pattern;
 mod register;
+mod scroll;
 mod sprite;
 
 use crate::cartridge::Cartridge;
+use crate::cdl;
 use background::NameTables;
-use log::{trace, warn};
+use log::trace;
 use palette::{Palettes, Rgb};
 use pattern::PatternTables;
 use register::{PPUCtrl, PPUMask, PPUStatus};
+use scroll::{advance_coarse_x, ScrollRegisters};
 use sprite::Sprite;
+use std::cell::RefCell;
 
 #[derive(Clone)]
 /// Picture Processing Unit. handle graphics.
@@ -18,39 +22,40 @@ pub struct Ppu {
     reg_ctrl: PPUCtrl,
     reg_mask: PPUMask,
     reg_status: PPUStatus,
-    pattern_tables: PatternTables,
     name_table: NameTables,
     palette_table: Palettes,
-    vram_addr: u16,
+    scroll: ScrollRegisters,
     oam_data: [u8; 0x100],
     oam_addr: u8,
-    scroll: (u8, u8),
-    last_store: (u16, u8),
     scanline: u16,
     cycles_in_line: u16,
     ppudata_buffer: u8,
+    io_latch: u8,
+    nmi_pending: bool,
+    frame_count: u64,
+    // dots since power on, and A12 of the address last put on the ppu bus
+    cycles: u64,
+    bus_a12: bool,
+    // pattern addresses of the 8 sprite fetches on the current line
+    sprite_fetches: [u16; 8],
     render_buffer: [u8; WINDOW_HEIGHT * WINDOW_WIDTH * 3],
 }
 
-/// Store execution result in a cycle
-pub struct ExecResult {
-    /// true if nmi interupt should happen
-    pub vblank_nmi: bool,
-    /// true if new frame starts
-    pub new_frame: bool,
-}
-
-impl ExecResult {
-    fn new() -> ExecResult {
-        ExecResult {
-            vblank_nmi: false,
-            new_frame: false,
-        }
-    }
-}
-
 const TOTAL_SCANLINE: u16 = 262;
 const TOTAL_CYCLES_IN_LINE: u16 = 341;
+const PRE_RENDER_SCANLINE: u16 = TOTAL_SCANLINE - 1;
+// PPU address bus is 14 bits wide
+const VRAM_ADDR_MASK: u16 = 0x3fff;
+// Dots where rendering reloads v from t, and where the next line's first tile is fetched
+const HORIZONTAL_COPY_CYCLE: u16 = 257;
+const VERTICAL_COPY_CYCLES: std::ops::RangeInclusive<u16> = 280..=304;
+const NEXT_LINE_FETCH_CYCLE: u16 = 320;
+// Sprite patterns are fetched for the next line in these cycles, background tiles in the others.
+// Each 8 cycle fetch reads the nametable at its first cycle and the pattern at its fifth.
+// Mappers watching PPU A12 (e.g. MMC3) see the edges between them.
+const SPRITE_FETCH_CYCLES: std::ops::RangeInclusive<u16> = 257..=320;
+const LAST_FETCH_CYCLE: u16 = 336;
+const EMPTY_SPRITE_TILE: u8 = 0xff;
 /// NES screen height
 pub const WINDOW_HEIGHT: usize = 240;
 /// NES screen width
@@ -62,35 +67,176 @@ impl Ppu {
         &self.render_buffer
     }
 
-    /// Execute single cycle
-    pub fn exec(&mut self) -> ExecResult {
-        let mut result = ExecResult::new();
+    /// Current scanline. 261 is the pre-render line.
+    pub fn scanline(&self) -> u16 {
+        self.scanline
+    }
 
+    /// Current dot in the scanline
+    pub fn dot(&self) -> u16 {
+        self.cycles_in_line
+    }
+
+    /// Number of frames started since power on
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// VRAM address accessed by the next $2007 load or store
+    pub fn vram_addr(&self) -> u16 {
+        self.scroll.vram_addr()
+    }
+
+    /// True if vblank NMI has been requested since the last call
+    pub fn take_nmi(&mut self) -> bool {
+        std::mem::replace(&mut self.nmi_pending, false)
+    }
+
+    /// Execute single cycle
+    pub fn exec(&mut self, cartridge: &mut Cartridge) {
+        self.cycles += 1;
         self.cycles_in_line = (self.cycles_in_line + 1) % TOTAL_CYCLES_IN_LINE;
+        if self.skips_last_dot() {
+            self.cycles_in_line = 0;
+        }
+        self.fetch(cartridge);
+        self.update_scroll();
         if self.cycles_in_line != 0 {
-            return result;
+            return;
         }
 
         if self.scanline < WINDOW_HEIGHT as u16 {
-            self.render_line(self.scanline as u8);
+            self.render_scanline(cartridge);
         }
+        self.scroll.start_next_line();
         if self.scanline == WINDOW_HEIGHT as u16 {
             self.reg_status.set_vblank(true);
             if self.reg_ctrl.vblank_nmi() {
-                result.vblank_nmi = true;
+                self.nmi_pending = true;
             }
         }
-        if self.scanline == TOTAL_SCANLINE - 1 {
+        if self.scanline == PRE_RENDER_SCANLINE {
             self.reg_status.set_vblank(false);
             self.reg_status.set_sprite_0_hit(false);
-            result.new_frame = true;
+            self.frame_count += 1;
         }
 
         self.scanline = (self.scanline + 1) % TOTAL_SCANLINE;
-        result
     }
 
-    fn render_line(&mut self, y: u8) {
+    // Pre-render line of odd frames is one dot shorter while rendering is enabled
+    fn skips_last_dot(&self) -> bool {
+        self.scanline == PRE_RENDER_SCANLINE
+            && self.cycles_in_line == TOTAL_CYCLES_IN_LINE - 1
+            && self.frame_count & 1 == 1
+            && self.rendering_enabled()
+    }
+
+    fn rendering_enabled(&self) -> bool {
+        self.reg_mask.show_background() || self.reg_mask.show_sprite()
+    }
+
+    // True while the PPU fetches tiles, so v follows the rendering position
+    fn is_rendering(&self) -> bool {
+        let rendering_line =
+            self.scanline < WINDOW_HEIGHT as u16 || self.scanline == PRE_RENDER_SCANLINE;
+        self.rendering_enabled() && rendering_line
+    }
+
+    // Rendering moves v along the fetched tiles and reloads it from t.
+    // The line renderer draws a whole line at once from where the line started.
+    fn update_scroll(&mut self) {
+        let dot = self.cycles_in_line;
+        if dot == NEXT_LINE_FETCH_CYCLE {
+            self.scroll.save_next_line_start();
+        }
+        if !self.is_rendering() {
+            return;
+        }
+        match dot {
+            256 => {
+                self.scroll.increment_coarse_x();
+                self.scroll.increment_y();
+            }
+            HORIZONTAL_COPY_CYCLE => self.scroll.copy_horizontal(),
+            dot if VERTICAL_COPY_CYCLES.contains(&dot) && self.scanline == PRE_RENDER_SCANLINE => {
+                self.scroll.copy_vertical()
+            }
+            8..=255 | 328 | 336 if dot & 0x07 == 0 => self.scroll.increment_coarse_x(),
+            _ => {}
+        }
+    }
+
+    // Let the cartridge see the address bus when A12 changes
+    fn put_address(&mut self, addr: u16, cartridge: &mut Cartridge) {
+        let a12 = addr & 0x1000 != 0;
+        if a12 != self.bus_a12 {
+            self.bus_a12 = a12;
+            cartridge.notify_ppu_address(addr, self.cycles);
+        }
+    }
+
+    // Put the addresses of the tile fetches on the bus. Lines are drawn by `render_line`.
+    fn fetch(&mut self, cartridge: &mut Cartridge) {
+        let dot = self.cycles_in_line;
+        if !self.is_rendering() || dot == 0 || dot > LAST_FETCH_CYCLE {
+            return;
+        }
+        if dot == *SPRITE_FETCH_CYCLES.start() {
+            self.evaluate_sprite_fetches();
+        }
+        let step = (dot - 1) & 0x07;
+        if step == 0 {
+            let nametable_addr = 0x2000 | (self.scroll.vram_addr() & 0x0fff);
+            self.put_address(nametable_addr, cartridge);
+        } else if step == 4 {
+            let pattern_addr = if SPRITE_FETCH_CYCLES.contains(&dot) {
+                let slot = usize::from(dot - SPRITE_FETCH_CYCLES.start()) / 8;
+                self.sprite_fetches[slot]
+            } else {
+                self.reg_ctrl.background_table().base_addr()
+            };
+            self.put_address(pattern_addr, cartridge);
+        }
+    }
+
+    // Pattern addresses of the sprites on the next line. Empty slots fetch tile $FF.
+    fn evaluate_sprite_fetches(&mut self) {
+        let next_line = match self.scanline {
+            PRE_RENDER_SCANLINE => 0,
+            scanline => scanline as u8 + 1,
+        };
+        let height = self.reg_ctrl.sprite_height();
+        let empty = sprite::pattern_addr(EMPTY_SPRITE_TILE, height, self.reg_ctrl.sprite_table());
+        self.sprite_fetches = [empty; 8];
+        let sprites = self
+            .get_sprites()
+            .into_iter()
+            .filter(|s| s.covers_line(next_line, height))
+            .take(8);
+        for (slot, sprite) in sprites.enumerate() {
+            self.sprite_fetches[slot] = sprite.pattern_addr(height, self.reg_ctrl.sprite_table());
+        }
+    }
+
+    // Render the current line, flagging the pattern bytes it used if the Code/Data Log runs
+    fn render_scanline(&mut self, cartridge: &mut Cartridge) {
+        let y = self.scanline as u8;
+        if cartridge.code_data_log().is_none() || !self.rendering_enabled() {
+            self.render_line(y, &PatternTables::new(cartridge));
+            return;
+        }
+        let fetches = RefCell::new(Vec::new());
+        self.render_line(y, &PatternTables::with_fetch_log(cartridge, &fetches));
+        let mut fetches = fetches.into_inner();
+        fetches.sort_unstable();
+        fetches.dedup();
+        for addr in fetches {
+            cartridge.log_chr(addr, cdl::CHR_RENDERED);
+        }
+    }
+
+    fn render_line(&mut self, y: u8, pattern_tables: &PatternTables) {
         // TODO: Use sprite priorities
         let sprites: Vec<Sprite> = self
             .get_sprites()
@@ -102,10 +248,10 @@ impl Ppu {
             let sprite0_color = sprite0.get_color(
                 x as u8,
                 y as u8,
-                self.pattern_tables.get_table(self.reg_ctrl.sprite_table()),
+                &pattern_tables.get_table(self.reg_ctrl.sprite_table()),
                 &self.palette_table,
             );
-            let bg_color = self.get_background_color(x as u8, y);
+            let bg_color = self.get_background_color(x as u8, pattern_tables);
 
             // TODO: sprite 0 condition is more complex.
             // See https://wiki.nesdev.com/w/index.php/PPU_OAM#Sprite_zero_hits<Paste>
@@ -114,7 +260,7 @@ impl Ppu {
             }
 
             let rgb = self
-                .get_sprite_color(&sprites, x as u8, y)
+                .get_sprite_color(&sprites, x as u8, y, pattern_tables)
                 .or(bg_color)
                 .unwrap_or_else(|| self.palette_table.get_universal_background_color());
             let index = 3 * (x + y as usize * WINDOW_WIDTH);
@@ -124,7 +270,13 @@ impl Ppu {
         }
     }
 
-    fn get_sprite_color(&self, sprites: &[Sprite], x: u8, y: u8) -> Option<Rgb> {
+    fn get_sprite_color(
+        &self,
+        sprites: &[Sprite],
+        x: u8,
+        y: u8,
+        pattern_tables: &PatternTables,
+    ) -> Option<Rgb> {
         // TODO: Read mask for
         // "Show sprites in leftmost 8 pixels of screen, 0: Hide"
         if !self.reg_mask.show_sprite() {
@@ -136,27 +288,28 @@ impl Ppu {
                 s.get_color(
                     x,
                     y,
-                    &self.pattern_tables.get_table(self.reg_ctrl.sprite_table()),
+                    &pattern_tables.get_table(self.reg_ctrl.sprite_table()),
                     &self.palette_table,
                 )
             })
             .next()
     }
 
-    fn get_background_color(&self, x: u8, y: u8) -> Option<Rgb> {
+    fn get_background_color(&self, x: u8, pattern_tables: &PatternTables) -> Option<Rgb> {
         // TODO: Read mask for
         // "Show sprites in leftmost 8 pixels of screen, 0: Hide"
         if !self.reg_mask.show_background() {
             return None;
         }
-        let (scroll_x, scroll_y) = self.get_scroll();
-        let (x, y) = (scroll_x + u16::from(x), scroll_y + u16::from(y));
-        let pattern_index = self.name_table.get_pattern_index(x, y);
-        let palette_index = self.name_table.get_palette_index(x, y);
-        let sprite_value = self
-            .pattern_tables
+        let (v, fine_x) = self.scroll.line_start();
+        let x = u16::from(fine_x) + u16::from(x);
+        let v = advance_coarse_x(v, x / 8);
+        let fine_y = (v >> 12) as u8;
+        let pattern_index = self.name_table.get_pattern_index(v);
+        let palette_index = self.name_table.get_palette_index(v);
+        let sprite_value = pattern_tables
             .get_table(self.reg_ctrl.background_table())
-            .get_value(pattern_index, (x % 8) as u8, (y % 8) as u8);
+            .get_value(pattern_index, (x % 8) as u8, fine_y);
         let rgb = self
             .palette_table
             .get_background_color(palette_index, sprite_value);
@@ -169,102 +322,108 @@ impl Ppu {
             .collect::<Vec<Sprite>>()
     }
 
-    fn get_scroll(&self) -> (u16, u16) {
-        let (low_x, low_y) = self.scroll;
-        let high_x = self.reg_ctrl.scroll_x_bit8();
-        let high_y = self.reg_ctrl.scroll_y_bit8();
-        (
-            (u16::from(high_x) << 8) | u16::from(low_x),
-            (u16::from(high_y) << 8) | u16::from(low_y),
-        )
-    }
-
     /// Create PPU from cartridge
     pub fn from_cartridge(cartridge: &Cartridge) -> Ppu {
-        Ppu::new(&cartridge.chr_rom)
+        let mut ppu = Ppu::new();
+        ppu.sync_mirroring(cartridge);
+        ppu
     }
 
-    fn new(chr_rom: &[u8]) -> Ppu {
+    /// Follow the cartridge mirroring, after a write that may have switched it
+    pub fn sync_mirroring(&mut self, cartridge: &Cartridge) {
+        self.name_table.set_mirroring(cartridge.mirroring());
+    }
+
+    fn new() -> Ppu {
         Ppu {
             reg_ctrl: PPUCtrl::new(),
             reg_mask: PPUMask::new(),
             reg_status: PPUStatus::new(),
-            last_store: (0, 0),
-            vram_addr: 0,
+            scroll: ScrollRegisters::new(),
             oam_data: [0; 0x100],
             oam_addr: 0,
-            scroll: (0, 0),
-            pattern_tables: PatternTables::new(chr_rom),
             name_table: NameTables::new(),
             palette_table: Palettes::new(),
             scanline: 0,
             cycles_in_line: 0,
             ppudata_buffer: 0,
+            io_latch: 0,
+            nmi_pending: false,
+            frame_count: 0,
+            cycles: 0,
+            bus_a12: false,
+            sprite_fetches: [0; 8],
             render_buffer: [0; WINDOW_HEIGHT * WINDOW_WIDTH * 3],
         }
     }
 
     /// load interface exposed to cpu via bus
-    pub fn load(&mut self, addr: u16) -> u8 {
+    pub fn load(&mut self, addr: u16, cartridge: &mut Cartridge) -> u8 {
         trace!("[Ppu] load addr={:#x}", addr);
-        match addr {
-            0x00 => {
-                warn!("It doesn't support load control register (0x00)");
-                0
-            }
-            0x01 => {
-                warn!("It doesn't support load mask register (0x01)");
-                0
-            }
+        let result = self.peek(addr, cartridge);
+        match addr & 0x07 {
             0x02 => {
-                let status = self.reg_status.to_u8();
                 self.reg_status.set_vblank(false);
-                status
-            }
-            0x03 => {
-                warn!("It doesn't support load oam addr (0x03)");
-                0
-            }
-            0x04 => {
-                // TODO: The actual behavior is much more complex.
-                // See https://wiki.nesdev.com/w/index.php/PPU_registers#OAMDATA
-                self.oam_data[self.oam_addr as usize]
-            }
-            0x05 => {
-                warn!("It doesn't support load ppu scroll (0x05)");
-                0
-            }
-            0x06 => {
-                warn!("It doesn't support load ppu addr (0x06)");
-                0
+                self.scroll.reset_toggle();
             }
             0x07 => {
-                let buf_result = self.ppudata_buffer;
-                let new_result = self.load_vram(self.vram_addr);
-                let result = match self.vram_addr {
-                    0x3f00...0x3fff => new_result,
-                    _ => buf_result,
-                };
-                self.vram_addr += u16::from(self.reg_ctrl.addr_incr());
-                self.ppudata_buffer = new_result;
-                result
+                let vram_addr = self.scroll.vram_addr();
+                self.put_address(vram_addr, cartridge);
+                self.ppudata_buffer = self.load_vram(vram_addr, cartridge);
+                if vram_addr < 0x2000 {
+                    cartridge.log_chr(vram_addr, cdl::CHR_READ);
+                }
+                self.increment_vram_addr();
             }
-            0x08...0xffff => panic!("Unknown address {}", addr),
+            _ => {}
+        }
+        self.io_latch = result;
+        result
+    }
+
+    /// Value `load` would return, without side effects
+    pub fn peek(&self, addr: u16, cartridge: &Cartridge) -> u8 {
+        match addr & 0x07 {
+            // lower 5 bits are not driven by the status register
+            0x02 => (self.reg_status.to_u8() & 0xe0) | (self.io_latch & 0x1f),
+            // TODO: The actual behavior is much more complex.
+            // See https://wiki.nesdev.com/w/index.php/PPU_registers#OAMDATA
+            0x04 => self.oam_data[self.oam_addr as usize],
+            // palette is returned immediately and others through the read buffer
+            0x07 => match self.scroll.vram_addr() {
+                0x3f00..=0x3fff => self.load_vram(self.scroll.vram_addr(), cartridge),
+                _ => self.ppudata_buffer,
+            },
+            // write-only registers return the value left on the PPU data bus
+            _ => self.io_latch,
         }
     }
 
+    /// Byte in ppu address space ($0000-$3FFF) without side effects
+    pub fn peek_vram(&self, addr: u16, cartridge: &Cartridge) -> u8 {
+        self.load_vram(addr & VRAM_ADDR_MASK, cartridge)
+    }
+
+    /// Byte in OAM
+    pub fn peek_oam(&self, index: u8) -> u8 {
+        self.oam_data[usize::from(index)]
+    }
+
     /// store interface exposed to cpu via bus
-    pub fn store(&mut self, addr: u16, val: u8) {
+    pub fn store(&mut self, addr: u16, val: u8, cartridge: &mut Cartridge) {
         trace!("Store addr={:#x} val={:#x}", addr, val);
+        let addr = addr & 0x07;
+        self.io_latch = val;
         match addr {
             0x00 => {
                 self.reg_ctrl.set_u8(val);
+                self.scroll.write_ctrl(val);
             }
             0x01 => {
                 self.reg_mask.set_u8(val);
             }
             0x02 => {
-                warn!("It doesn't support write to status register");
+                // status register is read-only
             }
             0x03 => {
                 self.oam_addr = val;
@@ -275,24 +434,31 @@ impl Ppu {
                 self.write_oam(val);
             }
             0x05 => {
-                // TODO: What if it's during rendering?
-                // https://wiki.nesdev.com/w/index.php/PPU_registers#Scroll_.28.242005.29_.3E.3E_write_x2
-                if let (0x05, x) = self.last_store {
-                    self.scroll = (x, val);
-                }
+                self.scroll.write_scroll(val);
             }
             0x06 => {
-                if let (0x06, high) = self.last_store {
-                    self.vram_addr = (u16::from(high) << 8) | u16::from(val);
+                self.scroll.write_addr(val);
+                // the second write puts the new address on the bus
+                if !self.scroll.write_toggle() {
+                    self.put_address(self.scroll.vram_addr(), cartridge);
                 }
             }
-            0x07 => {
-                self.store_vram(self.vram_addr, val);
-                self.vram_addr += u16::from(self.reg_ctrl.addr_incr());
+            _ => {
+                self.put_address(self.scroll.vram_addr(), cartridge);
+                self.store_vram(self.scroll.vram_addr(), val, cartridge);
+                self.increment_vram_addr();
             }
-            0x08...0xffff => panic!("Unknown address {}", addr),
         };
-        self.last_store = (addr, val);
+    }
+
+    fn increment_vram_addr(&mut self) {
+        if self.is_rendering() {
+            // $2007 access during rendering bumps both counters instead
+            self.scroll.increment_coarse_x();
+            self.scroll.increment_y();
+        } else {
+            self.scroll.increment(u16::from(self.reg_ctrl.addr_incr()));
+        }
     }
 
     /// Write value to OAM. Increment OAM address.
@@ -301,25 +467,25 @@ impl Ppu {
         self.oam_addr = self.oam_addr.wrapping_add(1);
     }
 
-    fn load_vram(&self, addr: u16) -> u8 {
+    fn load_vram(&self, addr: u16, cartridge: &Cartridge) -> u8 {
         trace!("Load(vram) addr={:#x}", addr);
         match addr {
-            0x0000...0x1fff => self.pattern_tables.load(addr),
-            0x2000...0x2fff => self.name_table.load(addr - 0x2000),
-            0x3000...0x3eff => self.name_table.load(addr - 0x3000),
-            0x3f00...0x3fff => self.palette_table.load(addr & 0x1f),
-            0x4000...0xffff => unreachable!(),
+            0x0000..=0x1fff => PatternTables::new(cartridge).load(addr),
+            0x2000..=0x2fff => self.name_table.load(addr - 0x2000),
+            0x3000..=0x3eff => self.name_table.load(addr - 0x3000),
+            0x3f00..=0x3fff => self.palette_table.load(addr & 0x1f),
+            0x4000..=0xffff => unreachable!(),
         }
     }
 
-    fn store_vram(&mut self, addr: u16, val: u8) {
+    fn store_vram(&mut self, addr: u16, val: u8, cartridge: &mut Cartridge) {
         trace!("Store(vram) addr = {:#x} val = {:#x}", addr, val);
         match addr {
-            0x0000...0x1fff => warn!("it doesn't support to write to pattern table {:04X}", addr),
-            0x2000...0x2fff => self.name_table.store(addr - 0x2000, val),
-            0x3000...0x3eff => self.name_table.store(addr - 0x3000, val),
-            0x3f00...0x3fff => self.palette_table.store(addr & 0x1f, val),
-            0x4000...0xffff => unreachable!(),
+            0x0000..=0x1fff => cartridge.ppu_store(addr, val),
+            0x2000..=0x2fff => self.name_table.store(addr - 0x2000, val),
+            0x3000..=0x3eff => self.name_table.store(addr - 0x3000, val),
+            0x3f00..=0x3fff => self.palette_table.store(addr & 0x1f, val),
+            0x4000..=0xffff => unreachable!(),
         }
     }
 }
@@ -1,19 +1,8 @@
+use crate::mapper::Mirroring;
+
 const TABLE_SIZE: u16 = 0x400;
 const NAME_TABLE_SIZE: u16 = 0x3c0;
 
-const TABLE_WIDTH: u16 = 256;
-const TABLE_HEIGHT: u16 = 240;
-const BLOCK_WIDTH: u16 = 8;
-const BLOCK_HEIGHT: u16 = 8;
-const ATTRIBUTE_BLOCK_WIDTH: u16 = 32;
-const ATTRIBUTE_BLOCK_HEIGHT: u16 = 32;
-
-#[derive(Clone, Copy)]
-enum MirroringMode {
-    Vertical,
-    Horizontal,
-}
-
 #[derive(Clone, Copy)]
 enum Screen {
     TopLeft,
@@ -33,77 +22,68 @@ impl Screen {
             _ => unreachable!(),
         }
     }
-
-    fn from_coord(x: u16, y: u16) -> Screen {
-        match (x < TABLE_WIDTH, y < TABLE_HEIGHT) {
-            (true, true) => Screen::TopLeft,
-            (false, true) => Screen::TopRight,
-            (true, false) => Screen::BottomLeft,
-            (false, false) => Screen::BottomRight,
-        }
-    }
 }
 
 #[derive(Clone)]
 pub struct NameTables {
-    table1: NameTable,
-    table2: NameTable,
-    mirroing: MirroringMode,
+    // the console has 2KB VRAM, four-screen boards add the other 2KB
+    tables: [NameTable; 4],
+    mirroing: Mirroring,
 }
 
 impl NameTables {
     pub fn new() -> NameTables {
         NameTables {
-            table1: NameTable::new(),
-            table2: NameTable::new(),
-            mirroing: MirroringMode::Vertical,
+            tables: [
+                NameTable::new(),
+                NameTable::new(),
+                NameTable::new(),
+                NameTable::new(),
+            ],
+            mirroing: Mirroring::Vertical,
         }
     }
 
-    pub fn get_pattern_index(&self, x: u16, y: u16) -> u8 {
-        let (x, y) = (x % (2 * TABLE_WIDTH), y % (2 * TABLE_HEIGHT));
-        self.get_table(Screen::from_coord(x, y))
-            .get_pattern_index(x % TABLE_WIDTH, y % TABLE_HEIGHT)
+    /// Tile at the VRAM address `v` of the scroll registers
+    pub fn get_pattern_index(&self, v: u16) -> u8 {
+        self.load(v & 0x0fff)
     }
 
-    pub fn get_palette_index(&self, x: u16, y: u16) -> u8 {
-        let (x, y) = (x % (2 * TABLE_WIDTH), y % (2 * TABLE_HEIGHT));
-        self.get_table(Screen::from_coord(x, y))
-            .get_palette_index(x % TABLE_WIDTH, y % TABLE_HEIGHT)
+    /// Palette of the tile at the VRAM address `v` of the scroll registers
+    pub fn get_palette_index(&self, v: u16) -> u8 {
+        // each attribute byte covers 4x4 tiles, 2 bits for each 2x2 tiles
+        let addr = (v & 0x0c00) | NAME_TABLE_SIZE | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+        let shift = ((v >> 4) & 0x04) | (v & 0x02);
+        (self.load(addr) >> shift) & 0x03
     }
 
-    pub fn set_varical_mirroring(&mut self) {
-        self.mirroing = MirroringMode::Vertical;
+    pub fn set_mirroring(&mut self, mirroring: Mirroring) {
+        self.mirroing = mirroring;
     }
 
-    pub fn set_horizontal_mirroring(&mut self) {
-        self.mirroing = MirroringMode::Horizontal;
+    fn table_index(&self, screen: Screen) -> usize {
+        match (self.mirroing, screen) {
+            (Mirroring::Vertical, Screen::TopLeft) => 0,
+            (Mirroring::Vertical, Screen::TopRight) => 1,
+            (Mirroring::Vertical, Screen::BottomLeft) => 0,
+            (Mirroring::Vertical, Screen::BottomRight) => 1,
+            (Mirroring::Horizontal, Screen::TopLeft) => 0,
+            (Mirroring::Horizontal, Screen::TopRight) => 0,
+            (Mirroring::Horizontal, Screen::BottomLeft) => 1,
+            (Mirroring::Horizontal, Screen::BottomRight) => 1,
+            (Mirroring::SingleScreenLower, _) => 0,
+            (Mirroring::SingleScreenUpper, _) => 1,
+            (Mirroring::FourScreen, screen) => screen as usize,
+        }
     }
 
     fn get_table(&self, screen: Screen) -> &NameTable {
-        match (self.mirroing, screen) {
-            (MirroringMode::Vertical, Screen::TopLeft) => &self.table1,
-            (MirroringMode::Vertical, Screen::TopRight) => &self.table2,
-            (MirroringMode::Vertical, Screen::BottomLeft) => &self.table1,
-            (MirroringMode::Vertical, Screen::BottomRight) => &self.table2,
-            (MirroringMode::Horizontal, Screen::TopLeft) => &self.table1,
-            (MirroringMode::Horizontal, Screen::TopRight) => &self.table1,
-            (MirroringMode::Horizontal, Screen::BottomLeft) => &self.table2,
-            (MirroringMode::Horizontal, Screen::BottomRight) => &self.table2,
-        }
+        &self.tables[self.table_index(screen)]
     }
 
     fn get_table_mut(&mut self, screen: Screen) -> &mut NameTable {
-        match (self.mirroing, screen) {
-            (MirroringMode::Vertical, Screen::TopLeft) => &mut self.table1,
-            (MirroringMode::Vertical, Screen::TopRight) => &mut self.table2,
-            (MirroringMode::Vertical, Screen::BottomLeft) => &mut self.table1,
-            (MirroringMode::Vertical, Screen::BottomRight) => &mut self.table2,
-            (MirroringMode::Horizontal, Screen::TopLeft) => &mut self.table1,
-            (MirroringMode::Horizontal, Screen::TopRight) => &mut self.table1,
-            (MirroringMode::Horizontal, Screen::BottomLeft) => &mut self.table2,
-            (MirroringMode::Horizontal, Screen::BottomRight) => &mut self.table2,
-        }
+        let index = self.table_index(screen);
+        &mut self.tables[index]
     }
 
     pub fn load(&self, addr: u16) -> u8 {
@@ -129,32 +109,6 @@ impl NameTable {
         }
     }
 
-    fn get_pattern_index(&self, x: u16, y: u16) -> u8 {
-        let block_x = x / BLOCK_WIDTH;
-        let block_y = y / BLOCK_HEIGHT;
-        let pitch = TABLE_WIDTH / BLOCK_WIDTH;
-        let addr = block_x + block_y * pitch;
-        self.memory[addr as usize]
-    }
-
-    fn get_palette_index(&self, x: u16, y: u16) -> u8 {
-        let attr_x = x / ATTRIBUTE_BLOCK_WIDTH;
-        let attr_y = y / ATTRIBUTE_BLOCK_HEIGHT;
-        let pitch = TABLE_WIDTH / ATTRIBUTE_BLOCK_WIDTH;
-        let attr_index = attr_x + attr_y * pitch;
-        let addr = NAME_TABLE_SIZE + attr_index;
-        let shift = match (
-            x % ATTRIBUTE_BLOCK_WIDTH < ATTRIBUTE_BLOCK_WIDTH / 2,
-            y % ATTRIBUTE_BLOCK_HEIGHT < ATTRIBUTE_BLOCK_HEIGHT / 2,
-        ) {
-            (true, true) => 0,
-            (false, true) => 2,
-            (true, false) => 4,
-            (false, false) => 6,
-        };
-        (self.memory[addr as usize] >> shift) % 4
-    }
-
     fn load(&self, addr: u16) -> u8 {
         self.memory[addr as usize]
     }
@@ -163,3 +117,25 @@ impl NameTable {
         self.memory[addr as usize] = value
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn four_screen_keeps_every_nametable_apart() {
+        let mut tables = NameTables::new();
+        tables.set_mirroring(Mirroring::FourScreen);
+        for (i, addr) in [0x000, 0x400, 0x800, 0xc00].iter().enumerate() {
+            tables.store(*addr, i as u8 + 1);
+        }
+        assert_eq!(tables.load(0x000), 1);
+        assert_eq!(tables.load(0x400), 2);
+        assert_eq!(tables.load(0x800), 3);
+        assert_eq!(tables.load(0xc00), 4);
+
+        tables.set_mirroring(Mirroring::Vertical);
+        assert_eq!(tables.load(0x800), 1);
+        assert_eq!(tables.load(0xc00), 2);
+    }
+}
@@ -1,14 +1,18 @@
-#[derive(Clone)]
-pub struct PatternTables {
-    left: PatternTable,
-    right: PatternTable,
+use crate::cartridge::Cartridge;
+use std::cell::RefCell;
+
+/// Pattern tables read through the cartridge mapper
+pub struct PatternTables<'a> {
+    cartridge: &'a Cartridge,
+    fetches: Option<&'a RefCell<Vec<u16>>>,
 }
 
-const PATTERN_TABLE_LENGTH: usize = 0x1000;
+const PATTERN_TABLE_LENGTH: u16 = 0x1000;
 
-#[derive(Clone)]
-pub struct PatternTable {
-    memory: [u8; PATTERN_TABLE_LENGTH],
+pub struct PatternTable<'a> {
+    cartridge: &'a Cartridge,
+    base: u16,
+    fetches: Option<&'a RefCell<Vec<u16>>>,
 }
 
 pub enum PatternTableSide {
@@ -17,56 +21,57 @@ pub enum PatternTableSide {
 }
 
 impl PatternTableSide {
-    fn from_addr(addr: u16) -> PatternTableSide {
-        let id = addr / PATTERN_TABLE_LENGTH as u16;
-        match id {
-            0 => PatternTableSide::Left,
-            1 => PatternTableSide::Right,
-            _ => unreachable!(),
+    pub fn base_addr(&self) -> u16 {
+        match self {
+            PatternTableSide::Left => 0,
+            PatternTableSide::Right => PATTERN_TABLE_LENGTH,
         }
     }
 }
 
-impl PatternTables {
-    pub fn new(chr_rom: &[u8]) -> Self {
-        assert_eq!(chr_rom.len(), 0x2000);
+impl<'a> PatternTables<'a> {
+    pub fn new(cartridge: &'a Cartridge) -> Self {
         PatternTables {
-            left: PatternTable::new(&chr_rom[0..0x1000]),
-            right: PatternTable::new(&chr_rom[0x1000..0x2000]),
+            cartridge,
+            fetches: None,
         }
     }
 
-    pub fn load(&self, addr: u16) -> u8 {
-        self.get_table(PatternTableSide::from_addr(addr))
-            .load(addr % PATTERN_TABLE_LENGTH as u16)
-    }
-
-    pub fn get_table(&self, side: PatternTableSide) -> &PatternTable {
-        match side {
-            PatternTableSide::Left => &self.left,
-            PatternTableSide::Right => &self.right,
+    /// Record addresses read for rendering into `fetches`
+    pub fn with_fetch_log(cartridge: &'a Cartridge, fetches: &'a RefCell<Vec<u16>>) -> Self {
+        PatternTables {
+            cartridge,
+            fetches: Some(fetches),
         }
     }
-}
 
-impl PatternTable {
-    pub fn new(d: &[u8]) -> PatternTable {
-        assert_eq!(d.len(), 0x1000);
-        let mut memory = [0; 0x1000];
-        memory.clone_from_slice(d);
-        PatternTable { memory }
+    pub fn load(&self, addr: u16) -> u8 {
+        self.cartridge.ppu_load(addr)
     }
 
-    pub fn load(&self, addr: u16) -> u8 {
-        self.memory[addr as usize]
+    pub fn get_table(&self, side: PatternTableSide) -> PatternTable<'a> {
+        PatternTable {
+            cartridge: self.cartridge,
+            base: side.base_addr(),
+            fetches: self.fetches,
+        }
     }
+}
 
+impl<'a> PatternTable<'a> {
     pub fn get_value(&self, index: u8, x: u8, y: u8) -> u8 {
         assert!(x < 8);
         assert!(y < 8);
-        let base = index as usize * 16;
-        let c1 = self.memory[base + y as usize] >> (7 - x) & 1;
-        let c2 = self.memory[base + y as usize + 8] >> (7 - x) & 1;
+        let base = self.base + u16::from(index) * 16;
+        let c1 = self.fetch(base + u16::from(y)) >> (7 - x) & 1;
+        let c2 = self.fetch(base + u16::from(y) + 8) >> (7 - x) & 1;
         (c2 << 1) | c1
     }
+
+    fn fetch(&self, addr: u16) -> u8 {
+        if let Some(fetches) = self.fetches {
+            fetches.borrow_mut().push(addr);
+        }
+        self.cartridge.ppu_load(addr)
+    }
 }
@@ -32,14 +32,6 @@ impl PPUCtrl {
         (self.value & 0x80) != 0
     }
 
-    pub fn scroll_x_bit8(&self) -> u8 {
-        (self.value & 0x01)
-    }
-
-    pub fn scroll_y_bit8(&self) -> u8 {
-        ((self.value >> 1) & 0x01)
-    }
-
     pub fn background_table(&self) -> PatternTableSide {
         if (self.value & 0x10) != 0 {
             PatternTableSide::Right
@@ -56,6 +48,15 @@ impl PPUCtrl {
         }
     }
 
+    /// 8 or 16
+    pub fn sprite_height(&self) -> u8 {
+        if (self.value & 0x20) != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
     pub fn set_u8(&mut self, value: u8) {
         self.value = value;
     }
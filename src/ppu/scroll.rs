@@ -0,0 +1,248 @@
+// Internal PPU address registers. See https://wiki.nesdev.com/w/index.php/PPU_scrolling
+//
+// v and t are laid out as yyy NN YYYYY XXXXX
+// (fine Y, nametable, coarse Y, coarse X).
+
+const COARSE_X: u16 = 0x001f;
+const COARSE_Y: u16 = 0x03e0;
+const NAMETABLE_X: u16 = 0x0400;
+const NAMETABLE_Y: u16 = 0x0800;
+const FINE_Y: u16 = 0x7000;
+const HORIZONTAL_BITS: u16 = NAMETABLE_X | COARSE_X;
+const VERTICAL_BITS: u16 = FINE_Y | NAMETABLE_Y | COARSE_Y;
+// PPU address bus is 14 bits wide
+const VRAM_ADDR_MASK: u16 = 0x3fff;
+
+/// Move v right by `tiles` tiles, wrapping into the next horizontal nametable
+pub fn advance_coarse_x(v: u16, tiles: u16) -> u16 {
+    let column = ((v & NAMETABLE_X) >> 5 | (v & COARSE_X)) + tiles;
+    (v & !HORIZONTAL_BITS) | ((column & 0x20) << 5) | (column & COARSE_X)
+}
+
+#[derive(Clone)]
+pub struct ScrollRegisters {
+    // current VRAM address
+    v: u16,
+    // temporary VRAM address, the top left of the screen
+    t: u16,
+    // fine X scroll
+    x: u8,
+    // write toggle shared by $2005 and $2006
+    w: bool,
+    // v and x at the start of the line being rendered and the line after it
+    line_start: (u16, u8),
+    next_line_start: (u16, u8),
+}
+
+impl ScrollRegisters {
+    pub fn new() -> ScrollRegisters {
+        ScrollRegisters {
+            v: 0,
+            t: 0,
+            x: 0,
+            w: false,
+            line_start: (0, 0),
+            next_line_start: (0, 0),
+        }
+    }
+
+    /// Address for $2007 accesses
+    pub fn vram_addr(&self) -> u16 {
+        self.v & VRAM_ADDR_MASK
+    }
+
+    /// $2000 write selects the base nametable
+    pub fn write_ctrl(&mut self, val: u8) {
+        self.t = (self.t & !(NAMETABLE_X | NAMETABLE_Y)) | (u16::from(val & 0x03) << 10);
+    }
+
+    /// $2002 read clears the write toggle
+    pub fn reset_toggle(&mut self) {
+        self.w = false;
+    }
+
+    /// True after the first of two $2005/$2006 writes
+    pub fn write_toggle(&self) -> bool {
+        self.w
+    }
+
+    /// $2005 write sets X scroll, then Y scroll
+    pub fn write_scroll(&mut self, val: u8) {
+        let val = u16::from(val);
+        if self.w {
+            self.t = (self.t & !(FINE_Y | COARSE_Y)) | ((val & 0x07) << 12) | ((val & 0xf8) << 2);
+        } else {
+            self.t = (self.t & !COARSE_X) | (val >> 3);
+            self.x = (val & 0x07) as u8;
+        }
+        self.w = !self.w;
+    }
+
+    /// $2006 write sets the high byte, then the low byte and copies t into v
+    pub fn write_addr(&mut self, val: u8) {
+        let val = u16::from(val);
+        if self.w {
+            self.t = (self.t & 0xff00) | val;
+            self.v = self.t;
+        } else {
+            self.t = (self.t & 0x00ff) | ((val & 0x3f) << 8);
+        }
+        self.w = !self.w;
+    }
+
+    /// Increment after $2007 access outside rendering
+    pub fn increment(&mut self, amount: u16) {
+        self.v = (self.v + amount) & 0x7fff;
+    }
+
+    pub fn increment_coarse_x(&mut self) {
+        self.v = advance_coarse_x(self.v, 1);
+    }
+
+    pub fn increment_y(&mut self) {
+        if self.v & FINE_Y != FINE_Y {
+            self.v += 0x1000;
+            return;
+        }
+        self.v &= !FINE_Y;
+        let coarse_y = (self.v & COARSE_Y) >> 5;
+        let coarse_y = match coarse_y {
+            29 => {
+                self.v ^= NAMETABLE_Y;
+                0
+            }
+            // rows 30 and 31 are attributes. Scrolling into them wraps without switching.
+            31 => 0,
+            _ => coarse_y + 1,
+        };
+        self.v = (self.v & !COARSE_Y) | (coarse_y << 5);
+    }
+
+    pub fn copy_horizontal(&mut self) {
+        self.v = (self.v & !HORIZONTAL_BITS) | (self.t & HORIZONTAL_BITS);
+    }
+
+    pub fn copy_vertical(&mut self) {
+        self.v = (self.v & !VERTICAL_BITS) | (self.t & VERTICAL_BITS);
+    }
+
+    /// Remember where the next line starts, before its first two tiles are fetched
+    pub fn save_next_line_start(&mut self) {
+        self.next_line_start = (self.v, self.x);
+    }
+
+    /// Move on to the line saved by `save_next_line_start`
+    pub fn start_next_line(&mut self) {
+        self.line_start = self.next_line_start;
+    }
+
+    /// v and fine X at the left edge of the line being rendered
+    pub fn line_start(&self) -> (u16, u8) {
+        self.line_start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coarse_y(v: u16) -> u16 {
+        (v & COARSE_Y) >> 5
+    }
+
+    #[test]
+    fn write_toggle_is_shared_by_scroll_and_addr() {
+        let mut scroll = ScrollRegisters::new();
+        // first $2005 write, then $2006 takes the second write and copies t into v
+        scroll.write_scroll(0x7d);
+        assert_eq!(scroll.x, 0x05);
+        scroll.write_addr(0x34);
+        assert_eq!(scroll.t & 0x00ff, 0x34);
+        assert_eq!(scroll.vram_addr(), scroll.t);
+        assert!(!scroll.w);
+
+        scroll.write_addr(0x21);
+        assert!(scroll.w);
+        scroll.reset_toggle();
+        // without the toggle reset this would be the low byte
+        scroll.write_addr(0x23);
+        scroll.write_addr(0x45);
+        assert_eq!(scroll.vram_addr(), 0x2345);
+    }
+
+    #[test]
+    fn scroll_writes() {
+        let mut scroll = ScrollRegisters::new();
+        scroll.write_ctrl(0x03);
+        scroll.write_scroll(0x7d);
+        scroll.write_scroll(0x5e);
+        // fine Y 6, nametable 3, coarse Y 11, coarse X 15
+        assert_eq!(scroll.t, 0x6c00 | (11 << 5) | 15);
+        assert_eq!(scroll.x, 5);
+    }
+
+    #[test]
+    fn first_addr_write_clears_bit_14() {
+        let mut scroll = ScrollRegisters::new();
+        scroll.write_scroll(0x00);
+        scroll.write_scroll(0xff);
+        assert_ne!(scroll.t & 0x4000, 0);
+        scroll.write_addr(0xff);
+        assert_eq!(scroll.t, 0x3f00 | (scroll.t & 0x00ff));
+        scroll.write_addr(0x10);
+        assert_eq!(scroll.v, 0x3f10);
+    }
+
+    #[test]
+    fn increment_y_wraps_at_row_29_into_the_other_nametable() {
+        let mut scroll = ScrollRegisters::new();
+        scroll.v = FINE_Y | (29 << 5);
+        scroll.increment_y();
+        assert_eq!(scroll.v, NAMETABLE_Y);
+
+        scroll.v = FINE_Y | NAMETABLE_Y | (29 << 5);
+        scroll.increment_y();
+        assert_eq!(scroll.v, 0);
+    }
+
+    #[test]
+    fn increment_y_wraps_at_row_31_without_switching() {
+        let mut scroll = ScrollRegisters::new();
+        scroll.v = FINE_Y | (31 << 5) | 7;
+        scroll.increment_y();
+        assert_eq!(scroll.v, 7);
+
+        // fine Y counts first, then coarse Y
+        scroll.v = 0x6000 | (3 << 5);
+        scroll.increment_y();
+        assert_eq!(scroll.v, FINE_Y | (3 << 5));
+        scroll.increment_y();
+        assert_eq!((scroll.v & FINE_Y, coarse_y(scroll.v)), (0, 4));
+    }
+
+    #[test]
+    fn coarse_x_wraps_into_the_other_nametable() {
+        assert_eq!(advance_coarse_x(30, 1), 31);
+        assert_eq!(advance_coarse_x(31, 1), NAMETABLE_X);
+        assert_eq!(advance_coarse_x(NAMETABLE_X | 31, 1), 0);
+        assert_eq!(advance_coarse_x(30, 4), NAMETABLE_X | 2);
+        // other bits are kept
+        let v = FINE_Y | NAMETABLE_Y | (12 << 5);
+        assert_eq!(advance_coarse_x(v | 31, 1), v | NAMETABLE_X);
+
+        let mut scroll = ScrollRegisters::new();
+        scroll.v = NAMETABLE_X | 31;
+        scroll.increment_coarse_x();
+        assert_eq!(scroll.v, 0);
+    }
+
+    #[test]
+    fn copies_from_t() {
+        let mut scroll = ScrollRegisters::new();
+        scroll.t = 0x7fff;
+        scroll.copy_horizontal();
+        assert_eq!(scroll.v, HORIZONTAL_BITS);
+        scroll.copy_vertical();
+        assert_eq!(scroll.v, 0x7fff);
+    }
+}
@@ -1,6 +1,6 @@
 use super::palette::Palettes;
 use super::palette::Rgb;
-use super::pattern::PatternTable;
+use super::pattern::{PatternTable, PatternTableSide};
 
 // TODO: support 8x16 sprite
 const SPRITE_WIDTH: u8 = 8;
@@ -22,6 +22,15 @@ impl SpriteAttribute {
     }
 }
 
+/// Pattern address of a sprite tile, also fetched for empty sprite slots with tile $FF
+pub fn pattern_addr(tile_index: u8, height: u8, table: PatternTableSide) -> u16 {
+    if height == 16 {
+        (u16::from(tile_index & 0x01) << 12) | (u16::from(tile_index & 0xfe) << 4)
+    } else {
+        table.base_addr() | (u16::from(tile_index) << 4)
+    }
+}
+
 pub struct Sprite {
     base_y: u8,
     base_x: u8,
@@ -45,8 +54,18 @@ impl Sprite {
     }
 
     pub fn is_render_line(&self, y: u8) -> bool {
+        self.covers_line(y, SPRITE_HEIGHT)
+    }
+
+    pub fn covers_line(&self, y: u8, height: u8) -> bool {
         let inner_y = i16::from(y) - i16::from(self.base_y);
-        inner_y >= 0 && inner_y < i16::from(SPRITE_HEIGHT)
+        inner_y >= 0 && inner_y < i16::from(height)
+    }
+
+    /// Address of the tile in the pattern tables.
+    /// 8x16 sprites select the table by bit 0 of the tile index.
+    pub fn pattern_addr(&self, height: u8, table: PatternTableSide) -> u16 {
+        pattern_addr(self.tile_index, height, table)
     }
 
     pub fn get_color(
@@ -0,0 +1,744 @@
+mod report;
+
+use crate::cpu::Registers;
+use crate::symbols::SymbolTable;
+use crate::Nes;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::rc::Rc;
+
+const OPCODE_BRK: u8 = 0x00;
+const OPCODE_JSR: u8 = 0x20;
+
+/// Number of frames kept for per-frame statistics by default
+pub const DEFAULT_FRAME_HISTORY: usize = 600;
+
+/// How a routine is entered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RoutineKind {
+    /// code running when profiling started, usually the main loop
+    Main,
+    /// called by JSR
+    Subroutine,
+    /// NMI handler
+    Nmi,
+    /// IRQ or BRK handler
+    Irq,
+}
+
+/// Entry point of a routine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Routine {
+    /// how the routine is entered
+    pub kind: RoutineKind,
+    /// cpu address of the first instruction
+    pub addr: u16,
+    /// PRG ROM offset of the first instruction. Tells apart routines in different banks.
+    pub prg_offset: Option<usize>,
+}
+
+/// Cycles spent in a routine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutineStats {
+    /// the routine
+    pub routine: Routine,
+    /// symbol label or `$XXXX`
+    pub name: String,
+    /// times the routine was entered
+    pub calls: u64,
+    /// cycles in the routine and everything it called
+    pub inclusive_cycles: u64,
+    /// cycles in instructions of the routine itself
+    pub exclusive_cycles: u64,
+}
+
+/// Cycles spent in one video frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameStats {
+    /// frame number counted by the PPU
+    pub frame: u64,
+    /// cpu cycles in the frame
+    pub cycles: u64,
+    /// cycles in NMI handlers and routines they called
+    pub nmi_cycles: u64,
+    /// cycles in IRQ and BRK handlers and routines they called
+    pub irq_cycles: u64,
+    /// routines which ran in the frame, by inclusive cycles
+    pub routines: Vec<RoutineStats>,
+}
+
+/// Routine on a call path. A routine called from two places has two nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallNode {
+    /// cycles spent on this path
+    pub stats: RoutineStats,
+    /// routines called from here, by inclusive cycles
+    pub children: Vec<CallNode>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counters {
+    calls: u64,
+    inclusive: u64,
+    exclusive: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    routine: Routine,
+    children: BTreeMap<Routine, usize>,
+    // The routine is also an ancestor, whose inclusive count already has these cycles
+    recursive: bool,
+    total: Counters,
+    frame: Counters,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Activation {
+    node: usize,
+    // the routine has returned once the stack pointer is back here
+    return_s: u8,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ContextCycles {
+    total: u64,
+    nmi: u64,
+    irq: u64,
+}
+
+/// What a `Nes::step` executed
+pub(crate) struct Step {
+    /// registers before the instruction
+    pub before: Registers,
+    /// registers after the instruction, before an interrupt is taken
+    pub after: Registers,
+    /// opcode of the instruction
+    pub opcode: u8,
+    /// cycles of the instruction and OAM DMA
+    pub cycles: u64,
+    /// interrupt taken after the instruction and its cycles
+    pub interrupt: Option<(RoutineKind, u64)>,
+}
+
+/// Attributes cpu cycles to routines entered by JSR, NMI and IRQ.
+/// Attach to `Nes` with `set_profiler`.
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    nodes: Vec<Node>,
+    stack: Vec<Activation>,
+    names: HashMap<Routine, String>,
+    symbols: Option<Rc<SymbolTable>>,
+    frame: Option<u64>,
+    frame_cycles: ContextCycles,
+    total_cycles: ContextCycles,
+    frames: VecDeque<FrameStats>,
+    frame_history: usize,
+    completed_frames: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler {
+            nodes: Vec::new(),
+            stack: Vec::new(),
+            names: HashMap::new(),
+            symbols: None,
+            frame: None,
+            frame_cycles: ContextCycles::default(),
+            total_cycles: ContextCycles::default(),
+            frames: VecDeque::new(),
+            frame_history: DEFAULT_FRAME_HISTORY,
+            completed_frames: 0,
+        }
+    }
+}
+
+impl Profiler {
+    /// Create empty profiler
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// Name routines with the symbols. Only routines entered afterwards are named.
+    pub fn set_symbols(&mut self, symbols: Rc<SymbolTable>) {
+        self.symbols = Some(symbols);
+    }
+
+    /// Keep statistics of the last `frames` frames
+    pub fn set_frame_history(&mut self, frames: usize) {
+        self.frame_history = frames;
+        self.trim_frames();
+    }
+
+    /// Cpu cycles since profiling started
+    pub fn cycles(&self) -> u64 {
+        self.total_cycles.total
+    }
+
+    /// Cycles in NMI handlers and routines they called
+    pub fn nmi_cycles(&self) -> u64 {
+        self.total_cycles.nmi
+    }
+
+    /// Cycles in IRQ and BRK handlers and routines they called
+    pub fn irq_cycles(&self) -> u64 {
+        self.total_cycles.irq
+    }
+
+    /// Number of frames which ended while profiling
+    pub fn completed_frames(&self) -> u64 {
+        self.completed_frames
+    }
+
+    /// Statistics of recent frames, oldest first
+    pub fn frames(&self) -> &VecDeque<FrameStats> {
+        &self.frames
+    }
+
+    /// Statistics of all routines, by inclusive cycles
+    pub fn routines(&self) -> Vec<RoutineStats> {
+        self.aggregate(|node| node.total)
+    }
+
+    /// Call tree rooted at the code running when profiling started
+    pub fn call_tree(&self) -> Option<CallNode> {
+        if self.nodes.is_empty() {
+            None
+        } else {
+            Some(self.call_node(0))
+        }
+    }
+
+    pub(crate) fn record(&mut self, nes: &Nes, step: &Step) {
+        if self.stack.is_empty() {
+            self.start(nes, step.before.pc);
+        }
+        self.add_cycles(step.cycles);
+        self.leave(step.after.s);
+        match step.opcode {
+            OPCODE_JSR => self.enter(nes, RoutineKind::Subroutine, step.after.pc, step.before.s),
+            OPCODE_BRK => self.enter(nes, RoutineKind::Irq, step.after.pc, step.before.s),
+            _ => {}
+        }
+        if let Some((kind, cycles)) = step.interrupt {
+            let handler = nes.cpu_registers().pc;
+            self.enter(nes, kind, handler, step.after.s);
+            self.add_cycles(cycles);
+        }
+
+        let frame = nes.frame_count();
+        if let Some(previous) = self.frame.filter(|&previous| previous != frame) {
+            self.finish_frame(previous);
+        }
+        self.frame = Some(frame);
+    }
+
+    fn start(&mut self, nes: &Nes, pc: u16) {
+        let routine = self.routine(nes, RoutineKind::Main, pc);
+        self.nodes.push(Node {
+            routine,
+            children: BTreeMap::new(),
+            recursive: false,
+            total: Counters {
+                calls: 1,
+                ..Counters::default()
+            },
+            frame: Counters {
+                calls: 1,
+                ..Counters::default()
+            },
+        });
+        // the root never returns
+        self.stack.push(Activation {
+            node: 0,
+            return_s: 0,
+        });
+    }
+
+    fn routine(&mut self, nes: &Nes, kind: RoutineKind, addr: u16) -> Routine {
+        let routine = Routine {
+            kind,
+            addr,
+            prg_offset: nes.cartridge().prg_rom_offset(addr),
+        };
+        let symbols = &self.symbols;
+        self.names.entry(routine).or_insert_with(|| {
+            symbols
+                .as_ref()
+                .and_then(|symbols| symbols.label(addr, nes))
+                .unwrap_or_else(|| format!("${:04X}", addr))
+        });
+        routine
+    }
+
+    fn enter(&mut self, nes: &Nes, kind: RoutineKind, addr: u16, return_s: u8) {
+        let routine = self.routine(nes, kind, addr);
+        let parent = self.stack.last().expect("profiler has no root").node;
+        let node = match self.nodes[parent].children.get(&routine) {
+            Some(&node) => node,
+            None => {
+                let recursive = self
+                    .stack
+                    .iter()
+                    .any(|activation| self.nodes[activation.node].routine == routine);
+                let node = self.nodes.len();
+                self.nodes.push(Node {
+                    routine,
+                    children: BTreeMap::new(),
+                    recursive,
+                    total: Counters::default(),
+                    frame: Counters::default(),
+                });
+                self.nodes[parent].children.insert(routine, node);
+                node
+            }
+        };
+        self.nodes[node].total.calls += 1;
+        self.nodes[node].frame.calls += 1;
+        self.stack.push(Activation { node, return_s });
+    }
+
+    // Routines return when the stack pointer goes back above their return address.
+    // This also ends routines which drop their return address or return by RTI.
+    fn leave(&mut self, s: u8) {
+        while self.stack.len() > 1 && s >= self.stack[self.stack.len() - 1].return_s {
+            self.stack.pop();
+        }
+    }
+
+    fn add_cycles(&mut self, cycles: u64) {
+        for activation in &self.stack {
+            let node = &mut self.nodes[activation.node];
+            node.total.inclusive += cycles;
+            node.frame.inclusive += cycles;
+        }
+        if let Some(activation) = self.stack.last() {
+            let node = &mut self.nodes[activation.node];
+            node.total.exclusive += cycles;
+            node.frame.exclusive += cycles;
+        }
+        let handler = self
+            .stack
+            .iter()
+            .rev()
+            .map(|activation| self.nodes[activation.node].routine.kind)
+            .find(|&kind| kind == RoutineKind::Nmi || kind == RoutineKind::Irq);
+        for context in &mut [&mut self.frame_cycles, &mut self.total_cycles] {
+            context.total += cycles;
+            match handler {
+                Some(RoutineKind::Nmi) => context.nmi += cycles,
+                Some(RoutineKind::Irq) => context.irq += cycles,
+                _ => {}
+            }
+        }
+    }
+
+    fn finish_frame(&mut self, frame: u64) {
+        let stats = FrameStats {
+            frame,
+            cycles: self.frame_cycles.total,
+            nmi_cycles: self.frame_cycles.nmi,
+            irq_cycles: self.frame_cycles.irq,
+            routines: self.aggregate(|node| node.frame),
+        };
+        self.frames.push_back(stats);
+        self.trim_frames();
+        for node in &mut self.nodes {
+            node.frame = Counters::default();
+        }
+        self.frame_cycles = ContextCycles::default();
+        self.completed_frames += 1;
+    }
+
+    fn trim_frames(&mut self) {
+        while self.frames.len() > self.frame_history {
+            self.frames.pop_front();
+        }
+    }
+
+    fn aggregate(&self, counters: impl Fn(&Node) -> Counters) -> Vec<RoutineStats> {
+        let mut by_routine = BTreeMap::<Routine, Counters>::new();
+        for node in &self.nodes {
+            let c = counters(node);
+            if c.calls == 0 && c.inclusive == 0 {
+                continue;
+            }
+            let sum = by_routine.entry(node.routine).or_default();
+            sum.calls += c.calls;
+            sum.exclusive += c.exclusive;
+            if !node.recursive {
+                sum.inclusive += c.inclusive;
+            }
+        }
+        let mut routines = by_routine
+            .into_iter()
+            .map(|(routine, c)| self.stats(routine, c))
+            .collect::<Vec<_>>();
+        sort_by_cycles(&mut routines, |stats| stats);
+        routines
+    }
+
+    fn call_node(&self, index: usize) -> CallNode {
+        let node = &self.nodes[index];
+        let mut children = node
+            .children
+            .values()
+            .map(|&child| self.call_node(child))
+            .collect::<Vec<_>>();
+        sort_by_cycles(&mut children, |child| &child.stats);
+        CallNode {
+            stats: self.stats(node.routine, node.total),
+            children,
+        }
+    }
+
+    fn stats(&self, routine: Routine, c: Counters) -> RoutineStats {
+        RoutineStats {
+            routine,
+            name: self.names[&routine].clone(),
+            calls: c.calls,
+            inclusive_cycles: c.inclusive,
+            exclusive_cycles: c.exclusive,
+        }
+    }
+}
+
+fn sort_by_cycles<T>(items: &mut [T], stats: impl Fn(&T) -> &RoutineStats) {
+    items.sort_by(|a, b| {
+        let (a, b) = (stats(a), stats(b));
+        b.inclusive_cycles
+            .cmp(&a.inclusive_cycles)
+            .then(a.routine.cmp(&b.routine))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cartridge;
+
+    const HANDLER: u16 = 0x8040;
+
+    // NROM cartridge with each piece of code at its cpu address.
+    // Reset starts at $8000, NMI and IRQ/BRK go to $8040.
+    fn nes_profiling(code: &[(u16, &[u8])]) -> Nes {
+        let mut prg = vec![0xea; 0x8000];
+        for (addr, bytes) in code {
+            let offset = usize::from(addr - 0x8000);
+            prg[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+        prg[0x7ffa..0x8000].copy_from_slice(&[0x40, 0x80, 0x00, 0x80, 0x40, 0x80]);
+        let mut nes = Nes::with_cartridge(Cartridge::nrom(&prg));
+        nes.set_profiler(Profiler::new());
+        nes
+    }
+
+    fn step_until(nes: &mut Nes, pc: u16) {
+        let frame = nes.frame_count();
+        while nes.cpu_registers().pc != pc {
+            assert!(nes.frame_count() - frame < 3, "${:04X} is not reached", pc);
+            nes.step();
+        }
+    }
+
+    fn stats(profiler: &Profiler, kind: RoutineKind, addr: u16) -> RoutineStats {
+        profiler
+            .routines()
+            .into_iter()
+            .find(|stats| stats.routine.kind == kind && stats.routine.addr == addr)
+            .unwrap()
+    }
+
+    fn counts(stats: &RoutineStats) -> (u64, u64, u64) {
+        (stats.calls, stats.inclusive_cycles, stats.exclusive_cycles)
+    }
+
+    // main calls outer, which calls inner. NMI handler calls inner too.
+    fn nested_calls_and_nmi() -> Nes {
+        nes_profiling(&[
+            (
+                0x8000,
+                &[
+                    0xa9, 0x80, // LDA #$80
+                    0x8d, 0x00, 0x20, // STA $2000
+                    0x20, 0x20, 0x80, // JSR outer
+                    0x4c, 0x08, 0x80, // JMP $8008
+                ],
+            ),
+            (
+                0x8020,
+                &[
+                    0x20, 0x30, 0x80, // outer: JSR inner
+                    0xea, // NOP
+                    0x60, // RTS
+                ],
+            ),
+            (
+                0x8030,
+                &[
+                    0xea, // inner: NOP
+                    0x60, // RTS
+                ],
+            ),
+            (
+                HANDLER,
+                &[
+                    0x20, 0x30, 0x80, // JSR inner
+                    0x40, // RTI
+                ],
+            ),
+        ])
+    }
+
+    #[test]
+    fn nested_subroutines_and_nmi() {
+        let mut nes = nested_calls_and_nmi();
+        step_until(&mut nes, HANDLER + 3);
+        nes.step();
+        assert_eq!(nes.cpu_registers().pc, 0x8008);
+        let profiler = nes.profiler().unwrap();
+
+        // JSR counts for the caller, RTS and RTI for the routine returning
+        let inner = stats(profiler, RoutineKind::Subroutine, 0x8030);
+        assert_eq!(counts(&inner), (2, 2 * (2 + 6), 2 * (2 + 6)));
+        let outer = stats(profiler, RoutineKind::Subroutine, 0x8020);
+        assert_eq!(counts(&outer), (1, 6 + 2 + 6 + 8, 6 + 2 + 6));
+        // the 7 cycles taking NMI count for the handler
+        let nmi = stats(profiler, RoutineKind::Nmi, HANDLER);
+        assert_eq!(counts(&nmi), (1, 7 + 6 + 6 + 8, 7 + 6 + 6));
+        assert_eq!(profiler.nmi_cycles(), 27);
+        assert_eq!(profiler.irq_cycles(), 0);
+
+        let main = stats(profiler, RoutineKind::Main, 0x8000);
+        assert_eq!(main.calls, 1);
+        assert_eq!(main.inclusive_cycles, profiler.cycles());
+        assert_eq!(main.exclusive_cycles, profiler.cycles() - 22 - 27);
+    }
+
+    #[test]
+    fn call_tree_has_a_node_per_call_path() {
+        let mut nes = nested_calls_and_nmi();
+        step_until(&mut nes, HANDLER + 3);
+        nes.step();
+        let root = nes.profiler().unwrap().call_tree().unwrap();
+        assert_eq!(root.stats.routine.kind, RoutineKind::Main);
+        let children = root
+            .children
+            .iter()
+            .map(|child| {
+                let grandchildren = child
+                    .children
+                    .iter()
+                    .map(|node| (node.stats.routine.addr, node.stats.inclusive_cycles))
+                    .collect::<Vec<_>>();
+                (child.stats.routine.addr, grandchildren)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            children,
+            vec![(HANDLER, vec![(0x8030, 8)]), (0x8020, vec![(0x8030, 8)])]
+        );
+    }
+
+    #[test]
+    fn brk_and_irq_are_attributed_to_irq_handlers() {
+        let mut nes = nes_profiling(&[
+            (
+                0x8000,
+                &[
+                    0x00, 0xea, // BRK
+                    0x58, // CLI
+                    0x4c, 0x03, 0x80, // JMP $8003
+                ],
+            ),
+            (
+                HANDLER,
+                &[
+                    0xad, 0x15, 0x40, // LDA $4015 acknowledges the frame IRQ
+                    0x40, // RTI
+                ],
+            ),
+        ]);
+        step_until(&mut nes, 0x8002);
+        let profiler = nes.profiler().unwrap();
+        // BRK counts for the caller like JSR
+        assert_eq!(
+            counts(&stats(profiler, RoutineKind::Irq, HANDLER)),
+            (1, 10, 10)
+        );
+        assert_eq!(profiler.irq_cycles(), 10);
+
+        // APU frame counter IRQ
+        step_until(&mut nes, HANDLER);
+        step_until(&mut nes, 0x8003);
+        let profiler = nes.profiler().unwrap();
+        assert_eq!(
+            counts(&stats(profiler, RoutineKind::Irq, HANDLER)),
+            (2, 27, 27)
+        );
+        assert_eq!(profiler.irq_cycles(), 27);
+        assert_eq!(profiler.nmi_cycles(), 0);
+    }
+
+    #[test]
+    fn recursive_calls_are_counted_once_in_inclusive_cycles() {
+        let mut nes = nes_profiling(&[
+            (
+                0x8000,
+                &[
+                    0xa2, 0x02, // LDX #$02
+                    0x20, 0x20, 0x80, // JSR $8020
+                    0x4c, 0x05, 0x80, // JMP $8005
+                ],
+            ),
+            (
+                0x8020,
+                &[
+                    0xca, // DEX
+                    0xf0, 0x03, // BEQ $8026
+                    0x20, 0x20, 0x80, // JSR $8020
+                    0x60, // RTS
+                ],
+            ),
+        ]);
+        step_until(&mut nes, 0x8005);
+        let profiler = nes.profiler().unwrap();
+        // outer call: DEX, BEQ, JSR, RTS. inner call: DEX, BEQ taken, RTS.
+        let (outer, inner) = (2 + 2 + 6 + 6, 2 + 3 + 6);
+        let routine = stats(profiler, RoutineKind::Subroutine, 0x8020);
+        assert_eq!(counts(&routine), (2, outer + inner, outer + inner));
+
+        let root = profiler.call_tree().unwrap();
+        let node = &root.children[0];
+        assert_eq!(node.stats.inclusive_cycles, outer + inner);
+        assert_eq!(node.children[0].stats.inclusive_cycles, inner);
+    }
+
+    #[test]
+    fn routines_end_when_the_stack_pointer_passes_the_return_address() {
+        let mut nes = nes_profiling(&[
+            (
+                0x8000,
+                &[
+                    0x20, 0x20, 0x80, // JSR $8020
+                ],
+            ),
+            (
+                0x8020,
+                &[
+                    0x48, // PHA
+                    0x68, // PLA
+                    0x68, // PLA
+                    0x68, // PLA drops the return address
+                    0x4c, 0x30, 0x80, // JMP $8030
+                ],
+            ),
+            (
+                0x8030,
+                &[
+                    0x4c, 0x30, 0x80, // JMP $8030
+                ],
+            ),
+        ]);
+        step_until(&mut nes, 0x8030);
+        nes.step();
+        let profiler = nes.profiler().unwrap();
+        // PHA and PLA stay inside. JMP is back in main.
+        let routine = stats(profiler, RoutineKind::Subroutine, 0x8020);
+        assert_eq!(counts(&routine), (1, 3 + 4 * 3, 3 + 4 * 3));
+        let main = stats(profiler, RoutineKind::Main, 0x8000);
+        assert_eq!(main.exclusive_cycles, 6 + 3 + 3);
+    }
+
+    fn idle_frames(frames: u64) -> Nes {
+        // JMP $8000
+        let mut nes = nes_profiling(&[(0x8000, &[0x4c, 0x00, 0x80])]);
+        let start = nes.frame_count();
+        while nes.frame_count() - start < frames {
+            nes.step();
+        }
+        // the frame change is noticed after the next step
+        nes.step();
+        nes
+    }
+
+    #[test]
+    fn frames_roll_over_and_history_is_trimmed() {
+        let mut nes = idle_frames(4);
+        let mut profiler = nes.take_profiler().unwrap();
+        assert_eq!(profiler.completed_frames(), 4);
+        let frames = profiler
+            .frames()
+            .iter()
+            .map(|f| f.frame)
+            .collect::<Vec<_>>();
+        let last = *frames.last().unwrap();
+        assert_eq!(frames, vec![last - 3, last - 2, last - 1, last]);
+        let frame_cycles = profiler.frames().iter().map(|f| f.cycles).sum::<u64>();
+        assert!(frame_cycles < profiler.cycles());
+        for frame in profiler.frames().iter().skip(1) {
+            // 341 * 262 / 3 dots, one less on odd frames while rendering
+            assert!(
+                (29_780..=29_781).contains(&frame.cycles),
+                "{}",
+                frame.cycles
+            );
+            assert_eq!(frame.routines.len(), 1);
+            assert_eq!(frame.routines[0].inclusive_cycles, frame.cycles);
+        }
+
+        profiler.set_frame_history(2);
+        let frames = profiler
+            .frames()
+            .iter()
+            .map(|f| f.frame)
+            .collect::<Vec<_>>();
+        assert_eq!(frames, vec![last - 1, last]);
+        assert_eq!(profiler.completed_frames(), 4);
+    }
+
+    #[test]
+    fn report_formats() {
+        let mut nes = nested_calls_and_nmi();
+        step_until(&mut nes, HANDLER + 3);
+        nes.step();
+        let profiler = nes.profiler().unwrap();
+
+        let mut text = Vec::new();
+        profiler.write_text(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            format!("{} cycles in 0 frames", profiler.cycles())
+        );
+        assert!(lines[1].starts_with("NMI 27 cycles"));
+        assert!(text.contains("  $8020 (subroutine)\n"));
+        assert!(text.contains("  $8040 (nmi)\n"));
+        // call tree indents children under main
+        assert!(text.contains("  call tree\n"));
+        assert!(text.contains("  $8000 (main)\n"));
+        assert!(text.contains("      $8030 (subroutine)\n"));
+
+        let mut json = Vec::new();
+        profiler.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with("{\n"));
+        assert!(json.ends_with("}\n"));
+        assert!(json.contains(&format!("\n  \"cycles\": {},\n", profiler.cycles())));
+        assert!(json.contains("\n  \"nmi_cycles\": 27,\n"));
+        assert!(json.contains("\n  \"completed_frames\": 0,\n"));
+        assert!(json.contains("\n  \"frames\": [],\n"));
+        assert!(json.contains(
+            "{\"name\": \"$8020\", \"kind\": \"subroutine\", \"addr\": 32800, \
+             \"prg_offset\": 32, \"calls\": 1, \"inclusive_cycles\": 22, \"exclusive_cycles\": 14"
+        ));
+        assert!(json.contains("\n  \"call_tree\": {\"name\": \"$8000\", \"kind\": \"main\""));
+        let depth = json.chars().fold(0, |depth, c| match c {
+            '{' | '[' => depth + 1,
+            '}' | ']' => depth - 1,
+            _ => depth,
+        });
+        assert_eq!(depth, 0);
+    }
+}
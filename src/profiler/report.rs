@@ -0,0 +1,229 @@
+use super::{CallNode, FrameStats, Profiler, Routine, RoutineKind, RoutineStats};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+fn kind_name(kind: RoutineKind) -> &'static str {
+    match kind {
+        RoutineKind::Main => "main",
+        RoutineKind::Subroutine => "subroutine",
+        RoutineKind::Nmi => "nmi",
+        RoutineKind::Irq => "irq",
+    }
+}
+
+fn percent(cycles: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        cycles as f64 * 100.0 / total as f64
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_routine(stats: &RoutineStats) -> String {
+    let prg_offset = stats
+        .routine
+        .prg_offset
+        .map(|offset| offset.to_string())
+        .unwrap_or_else(|| "null".to_string());
+    format!(
+        "\"name\": {}, \"kind\": \"{}\", \"addr\": {}, \"prg_offset\": {}, \
+         \"calls\": {}, \"inclusive_cycles\": {}, \"exclusive_cycles\": {}",
+        json_string(&stats.name),
+        kind_name(stats.routine.kind),
+        stats.routine.addr,
+        prg_offset,
+        stats.calls,
+        stats.inclusive_cycles,
+        stats.exclusive_cycles
+    )
+}
+
+fn write_json_routines<W: Write>(
+    w: &mut W,
+    routines: &[RoutineStats],
+    indent: &str,
+) -> io::Result<()> {
+    writeln!(w, "[")?;
+    for (i, stats) in routines.iter().enumerate() {
+        let comma = if i + 1 < routines.len() { "," } else { "" };
+        writeln!(w, "{}  {{{}}}{}", indent, json_routine(stats), comma)?;
+    }
+    write!(w, "{}]", indent)
+}
+
+fn write_json_frame<W: Write>(w: &mut W, frame: &FrameStats) -> io::Result<()> {
+    writeln!(w, "    {{")?;
+    writeln!(w, "      \"frame\": {},", frame.frame)?;
+    writeln!(w, "      \"cycles\": {},", frame.cycles)?;
+    writeln!(w, "      \"nmi_cycles\": {},", frame.nmi_cycles)?;
+    writeln!(w, "      \"irq_cycles\": {},", frame.irq_cycles)?;
+    write!(w, "      \"routines\": ")?;
+    write_json_routines(w, &frame.routines, "      ")?;
+    write!(w, "\n    }}")
+}
+
+fn write_json_node<W: Write>(w: &mut W, node: &CallNode, depth: usize) -> io::Result<()> {
+    let indent = "  ".repeat(depth + 1);
+    write!(w, "{{{}, \"children\": [", json_routine(&node.stats))?;
+    for (i, child) in node.children.iter().enumerate() {
+        let comma = if i + 1 < node.children.len() { "," } else { "" };
+        write!(w, "\n{}  ", indent)?;
+        write_json_node(w, child, depth + 1)?;
+        write!(w, "{}", comma)?;
+    }
+    if !node.children.is_empty() {
+        write!(w, "\n{}", indent)?;
+    }
+    write!(w, "]}}")
+}
+
+fn write_text_node<W: Write>(
+    w: &mut W,
+    node: &CallNode,
+    depth: usize,
+    total: u64,
+) -> io::Result<()> {
+    let stats = &node.stats;
+    writeln!(
+        w,
+        "{:>12} {:>6.2}% {:>12} {:>8}  {}{} ({})",
+        stats.inclusive_cycles,
+        percent(stats.inclusive_cycles, total),
+        stats.exclusive_cycles,
+        stats.calls,
+        "  ".repeat(depth),
+        stats.name,
+        kind_name(stats.routine.kind)
+    )?;
+    for child in &node.children {
+        write_text_node(w, child, depth + 1, total)?;
+    }
+    Ok(())
+}
+
+impl Profiler {
+    // Largest inclusive cycles of each routine in a single frame of the history
+    fn max_frame_cycles(&self) -> HashMap<Routine, u64> {
+        let mut max = HashMap::new();
+        for frame in &self.frames {
+            for stats in &frame.routines {
+                let cycles = max.entry(stats.routine).or_insert(0);
+                *cycles = stats.inclusive_cycles.max(*cycles);
+            }
+        }
+        max
+    }
+
+    /// Write a report as a table of routines and a call tree
+    pub fn write_text<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let total = self.cycles();
+        let frames = self.completed_frames;
+        writeln!(w, "{} cycles in {} frames", total, frames)?;
+        writeln!(
+            w,
+            "NMI {} cycles ({:.2}%), IRQ {} cycles ({:.2}%)",
+            self.nmi_cycles(),
+            percent(self.nmi_cycles(), total),
+            self.irq_cycles(),
+            percent(self.irq_cycles(), total)
+        )?;
+        let busiest = self
+            .frames
+            .iter()
+            .max_by_key(|frame| frame.cycles - frame.nmi_cycles);
+        if let Some(busiest) = busiest {
+            let average = self.frames.iter().map(|frame| frame.cycles).sum::<u64>()
+                / self.frames.len() as u64;
+            writeln!(
+                w,
+                "{} cycles per frame on average in the last {} frames, \
+                 frame {} spent the most outside NMI: {} cycles",
+                average,
+                self.frames.len(),
+                busiest.frame,
+                busiest.cycles - busiest.nmi_cycles
+            )?;
+        }
+
+        writeln!(w)?;
+        writeln!(
+            w,
+            "{:>12} {:>7} {:>12} {:>7} {:>8} {:>10} {:>10}  routine",
+            "inclusive", "%", "exclusive", "%", "calls", "avg/frame", "max/frame"
+        )?;
+        let max_frame_cycles = self.max_frame_cycles();
+        for stats in self.routines() {
+            let average = stats
+                .inclusive_cycles
+                .checked_div(frames)
+                .unwrap_or(stats.inclusive_cycles);
+            writeln!(
+                w,
+                "{:>12} {:>6.2}% {:>12} {:>6.2}% {:>8} {:>10} {:>10}  {} ({})",
+                stats.inclusive_cycles,
+                percent(stats.inclusive_cycles, total),
+                stats.exclusive_cycles,
+                percent(stats.exclusive_cycles, total),
+                stats.calls,
+                average,
+                max_frame_cycles.get(&stats.routine).cloned().unwrap_or(0),
+                stats.name,
+                kind_name(stats.routine.kind)
+            )?;
+        }
+
+        if let Some(root) = self.call_tree() {
+            writeln!(w)?;
+            writeln!(
+                w,
+                "{:>12} {:>7} {:>12} {:>8}  call tree",
+                "inclusive", "%", "exclusive", "calls"
+            )?;
+            write_text_node(&mut w, &root, 0, total)?;
+        }
+        Ok(())
+    }
+
+    /// Write a report in JSON with totals, routines, per-frame statistics and the call tree
+    pub fn write_json<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "{{")?;
+        writeln!(w, "  \"cycles\": {},", self.cycles())?;
+        writeln!(w, "  \"nmi_cycles\": {},", self.nmi_cycles())?;
+        writeln!(w, "  \"irq_cycles\": {},", self.irq_cycles())?;
+        writeln!(w, "  \"completed_frames\": {},", self.completed_frames)?;
+        write!(w, "  \"routines\": ")?;
+        write_json_routines(&mut w, &self.routines(), "  ")?;
+        writeln!(w, ",")?;
+        write!(w, "  \"frames\": [")?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            writeln!(w, "{}", if i == 0 { "" } else { "," })?;
+            write_json_frame(&mut w, frame)?;
+        }
+        if !self.frames.is_empty() {
+            write!(w, "\n  ")?;
+        }
+        writeln!(w, "],")?;
+        write!(w, "  \"call_tree\": ")?;
+        match self.call_tree() {
+            Some(root) => write_json_node(&mut w, &root, 0)?,
+            None => write!(w, "null")?,
+        }
+        writeln!(w, "\n}}")
+    }
+}
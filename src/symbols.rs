@@ -0,0 +1,240 @@
+mod ca65;
+mod error;
+mod fceux;
+
+pub use self::error::SymbolError;
+
+use crate::disasm::MemoryView;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// Code labels name addresses up to this far after them, e.g. `reset_handler+3`
+const CODE_LABEL_REACH: usize = 0x100;
+
+/// Named address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// label name
+    pub name: String,
+    /// cpu address
+    pub addr: u16,
+    /// bytes covered by the label. Addresses inside print as `name+offset`.
+    pub size: u16,
+    /// offset in PRG ROM for labels in a bank. None for RAM.
+    pub prg_offset: Option<usize>,
+}
+
+impl Symbol {
+    fn reach(&self) -> usize {
+        if self.prg_offset.is_some() || self.addr >= 0x8000 {
+            usize::from(self.size).max(CODE_LABEL_REACH)
+        } else {
+            usize::from(self.size).max(1)
+        }
+    }
+}
+
+/// Labels loaded from ca65 `.dbg` and FCEUX `.nl` files
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    // indices into `symbols`. The first symbol at a location wins.
+    by_addr: BTreeMap<u16, usize>,
+    by_prg_offset: BTreeMap<usize, usize>,
+}
+
+impl SymbolTable {
+    /// Create empty table
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    /// Add a symbol
+    pub fn add(&mut self, symbol: Symbol) {
+        let index = self.symbols.len();
+        self.by_addr.entry(symbol.addr).or_insert(index);
+        if let Some(offset) = symbol.prg_offset {
+            self.by_prg_offset.entry(offset).or_insert(index);
+        }
+        self.symbols.push(symbol);
+    }
+
+    /// Load a file by its name: `*.dbg` from ld65, `*.ram.nl` and `*.<bank>.nl` from FCEUX.
+    /// FCEUX bank numbers are hexadecimal and count 16KB PRG ROM banks.
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), SymbolError> {
+        let path = path.as_ref();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let unknown = || SymbolError::UnknownFormat {
+            path: path.display().to_string(),
+        };
+        if name.ends_with(".dbg") {
+            return self.load_ca65_dbg(&fs::read_to_string(path)?);
+        }
+        let stem = name.strip_suffix(".nl").ok_or_else(unknown)?;
+        let bank = match stem.rsplit('.').next() {
+            Some("ram") => None,
+            Some(bank) => Some(usize::from_str_radix(bank, 16).map_err(|_| unknown())?),
+            None => return Err(unknown()),
+        };
+        self.load_fceux_nl(&fs::read_to_string(path)?, bank)
+    }
+
+    /// Load labels from ld65 debug info (`ld65 --dbgfile`)
+    pub fn load_ca65_dbg(&mut self, text: &str) -> Result<(), SymbolError> {
+        for symbol in ca65::parse(text)? {
+            self.add(symbol);
+        }
+        Ok(())
+    }
+
+    /// Load an FCEUX label file. `bank` is the 16KB PRG ROM bank, or None for `.ram.nl`.
+    pub fn load_fceux_nl(&mut self, text: &str, bank: Option<usize>) -> Result<(), SymbolError> {
+        for symbol in fceux::parse(text, bank)? {
+            self.add(symbol);
+        }
+        Ok(())
+    }
+
+    /// All symbols in load order
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// True if no symbol is loaded
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Symbol with the name
+    pub fn find(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    /// Address of `name` or `name+offset`. Offset is decimal or `$` hexadecimal.
+    pub fn resolve(&self, expr: &str) -> Option<u16> {
+        let mut parts = expr.splitn(2, '+');
+        let symbol = self.find(parts.next()?.trim())?;
+        let offset = match parts.next().map(str::trim) {
+            Some(offset) => match offset.strip_prefix('$') {
+                Some(hex) => u16::from_str_radix(hex, 16).ok()?,
+                None => offset.parse().ok()?,
+            },
+            None => 0,
+        };
+        Some(symbol.addr.wrapping_add(offset))
+    }
+
+    /// Symbol covering the cpu address and the offset from it.
+    /// Labels in PRG ROM are matched with the bank currently mapped in memory.
+    pub fn lookup(&self, addr: u16, memory: &impl MemoryView) -> Option<(&Symbol, u16)> {
+        if let Some(offset) = memory.prg_rom_offset(addr) {
+            let found = self.by_prg_offset.range(..=offset).next_back();
+            if let Some((start, index)) = found {
+                let symbol = &self.symbols[*index];
+                if offset - start < symbol.reach() {
+                    return Some((symbol, (offset - start) as u16));
+                }
+            }
+        }
+        let (start, index) = self.by_addr.range(..=addr).next_back()?;
+        let symbol = &self.symbols[*index];
+        let distance = usize::from(addr - start);
+        // labels in other banks are already checked by PRG ROM offset
+        let banked = symbol.prg_offset.is_some() && memory.prg_rom_offset(addr).is_some();
+        if !banked && distance < symbol.reach() {
+            Some((symbol, distance as u16))
+        } else {
+            None
+        }
+    }
+
+    /// `name` or `name+offset` for the cpu address
+    pub fn label(&self, addr: u16, memory: &impl MemoryView) -> Option<String> {
+        self.lookup(addr, memory).map(|(symbol, offset)| {
+            if offset == 0 {
+                symbol.name.clone()
+            } else {
+                format!("{}+{}", symbol.name, offset)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 16KB PRG ROM bank switched at $8000
+    struct BankedMemory {
+        bank: usize,
+    }
+
+    impl MemoryView for BankedMemory {
+        fn peek(&self, _addr: u16) -> u8 {
+            0
+        }
+
+        fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+            match addr {
+                0x8000..=0xbfff => Some(self.bank * 0x4000 + usize::from(addr - 0x8000)),
+                _ => None,
+            }
+        }
+    }
+
+    fn symbol(name: &str, addr: u16, size: u16, prg_offset: Option<usize>) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            addr,
+            size,
+            prg_offset,
+        }
+    }
+
+    fn lookup(table: &SymbolTable, addr: u16, bank: usize) -> Option<(&str, u16)> {
+        table
+            .lookup(addr, &BankedMemory { bank })
+            .map(|(symbol, offset)| (symbol.name.as_str(), offset))
+    }
+
+    #[test]
+    fn same_address_in_two_banks() {
+        let mut table = SymbolTable::new();
+        table.add(symbol("title_main", 0x8000, 1, Some(0x0000)));
+        table.add(symbol("game_main", 0x8000, 1, Some(0x4000)));
+        assert_eq!(lookup(&table, 0x8000, 0), Some(("title_main", 0)));
+        assert_eq!(lookup(&table, 0x8000, 1), Some(("game_main", 0)));
+        assert_eq!(lookup(&table, 0x8005, 1), Some(("game_main", 5)));
+        // neither label is in bank 2
+        assert_eq!(lookup(&table, 0x8000, 2), None);
+    }
+
+    #[test]
+    fn ram_labels_cover_their_size() {
+        let mut table = SymbolTable::new();
+        table
+            .load_fceux_nl("$0300/10#buffer#\n$0010#counter#\n", None)
+            .unwrap();
+        assert_eq!(lookup(&table, 0x0300, 0), Some(("buffer", 0)));
+        assert_eq!(lookup(&table, 0x030f, 0), Some(("buffer", 15)));
+        assert_eq!(lookup(&table, 0x0310, 0), None);
+        assert_eq!(lookup(&table, 0x0010, 0), Some(("counter", 0)));
+        assert_eq!(lookup(&table, 0x0011, 0), None);
+    }
+
+    #[test]
+    fn labels_and_resolve() {
+        let mut table = SymbolTable::new();
+        table.add(symbol("reset", 0xc000, 1, None));
+        let memory = BankedMemory { bank: 0 };
+        assert_eq!(table.label(0xc000, &memory), Some("reset".to_string()));
+        assert_eq!(table.label(0xc003, &memory), Some("reset+3".to_string()));
+        assert_eq!(table.resolve("reset+3"), Some(0xc003));
+        assert_eq!(table.resolve("reset + $10"), Some(0xc010));
+        assert_eq!(table.resolve("unknown"), None);
+    }
+}
@@ -0,0 +1,191 @@
+use super::{Symbol, SymbolError};
+use std::collections::HashMap;
+
+// Segment offsets in the output file include the iNES header
+const INES_HEADER_LENGTH: u32 = 16;
+
+struct Segment {
+    start: u32,
+    size: u32,
+    // offset in the output file. None for RAM segments.
+    file_offset: Option<u32>,
+}
+
+struct Label {
+    name: String,
+    value: u32,
+    size: u32,
+    segment: Option<u32>,
+}
+
+// Split `id=0,name="a,b",size=3` into pairs. Quotes are removed.
+fn parse_attributes(s: &str) -> HashMap<&str, &str> {
+    let mut attributes = HashMap::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let eq = match rest.find('=') {
+            Some(eq) => eq,
+            None => break,
+        };
+        let key = &rest[..eq];
+        rest = &rest[eq + 1..];
+        let value = if rest.starts_with('"') {
+            let end = rest[1..].find('"').map_or(rest.len(), |i| i + 1);
+            let value = &rest[1..end];
+            rest = &rest[(end + 1).min(rest.len())..];
+            value
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            let value = &rest[..end];
+            rest = &rest[end..];
+            value
+        };
+        rest = rest.trim_start_matches(',');
+        attributes.insert(key, value);
+    }
+    attributes
+}
+
+fn parse_number(s: &str) -> Option<u32> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+// Read segments and labels (`sym` records with `type=lab`) from ld65 debug info
+pub fn parse(text: &str) -> Result<Vec<Symbol>, SymbolError> {
+    let mut segments = HashMap::new();
+    let mut labels = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let error = |message: String| SymbolError::Parse {
+            line: i + 1,
+            message,
+        };
+        let line = line.trim();
+        let (record, attributes) = match line.find(char::is_whitespace) {
+            Some(space) => (&line[..space], parse_attributes(line[space..].trim())),
+            None => continue,
+        };
+        let number = |key: &str| -> Result<Option<u32>, SymbolError> {
+            match attributes.get(key) {
+                Some(value) => parse_number(value)
+                    .map(Some)
+                    .ok_or_else(|| error(format!("invalid {}: {}", key, value))),
+                None => Ok(None),
+            }
+        };
+        let required = |key: &str| -> Result<u32, SymbolError> {
+            number(key)?.ok_or_else(|| error(format!("{} record without {}", record, key)))
+        };
+        match record {
+            "seg" => {
+                let segment = Segment {
+                    start: required("start")?,
+                    size: required("size")?,
+                    file_offset: number("ooffs")?,
+                };
+                segments.insert(required("id")?, segment);
+            }
+            "sym" if attributes.get("type") == Some(&"lab") => {
+                let name = attributes
+                    .get("name")
+                    .ok_or_else(|| error("sym record without name".to_string()))?;
+                labels.push(Label {
+                    name: name.to_string(),
+                    value: required("val")?,
+                    size: number("size")?.unwrap_or(1),
+                    segment: number("seg")?,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let symbols = labels
+        .into_iter()
+        .filter(|label| label.value <= 0xffff)
+        .map(|label| {
+            let prg_offset = label
+                .segment
+                .and_then(|id| segments.get(&id))
+                .and_then(|segment| {
+                    let file_offset = segment.file_offset?;
+                    let in_segment =
+                        segment.start <= label.value && label.value < segment.start + segment.size;
+                    if !in_segment || file_offset < INES_HEADER_LENGTH {
+                        return None;
+                    }
+                    let offset = file_offset - INES_HEADER_LENGTH + (label.value - segment.start);
+                    Some(offset as usize)
+                });
+            Symbol {
+                name: label.name,
+                addr: label.value as u16,
+                size: label.size.min(0xffff) as u16,
+                prg_offset,
+            }
+        })
+        .collect();
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DBG: &str = "\
+version\tmajor=2,minor=0
+file\tid=0,name=\"src/main, part 1.s\",size=120,mtime=0x5f5e1000,mod=0
+seg\tid=0,name=\"ZEROPAGE\",start=0x000000,size=0x0010,addrsize=zeropage,type=rw
+seg\tid=1,name=\"CODE\",start=0x008000,size=0x4000,addrsize=absolute,type=ro,oname=\"game.nes\",ooffs=16
+seg\tid=2,name=\"BANK1\",start=0x008000,size=0x4000,addrsize=absolute,type=ro,oname=\"game.nes\",ooffs=16400
+sym\tid=0,name=\"counter\",addrsize=zeropage,scope=0,def=1,val=0x2,seg=0,type=lab
+sym\tid=1,name=\"reset\",addrsize=absolute,size=3,scope=0,def=2,val=0x8000,seg=1,type=lab
+sym\tid=2,name=\"bank1_entry\",addrsize=absolute,scope=0,def=3,val=0x8010,seg=2,type=lab
+sym\tid=3,name=\"SPEED\",addrsize=zeropage,scope=0,def=4,val=0x4,type=equ
+";
+
+    #[test]
+    fn quoted_values_may_contain_commas() {
+        let attributes = parse_attributes("id=0,name=\"a,b\",size=3");
+        assert_eq!(attributes.get("id"), Some(&"0"));
+        assert_eq!(attributes.get("name"), Some(&"a,b"));
+        assert_eq!(attributes.get("size"), Some(&"3"));
+    }
+
+    #[test]
+    fn labels_with_prg_offsets() {
+        let symbols = parse(DBG).unwrap();
+        let expected = vec![
+            Symbol {
+                name: "counter".to_string(),
+                addr: 0x0002,
+                size: 1,
+                prg_offset: None,
+            },
+            Symbol {
+                name: "reset".to_string(),
+                addr: 0x8000,
+                size: 3,
+                prg_offset: Some(0),
+            },
+            // ooffs includes the 16 byte iNES header
+            Symbol {
+                name: "bank1_entry".to_string(),
+                addr: 0x8010,
+                size: 1,
+                prg_offset: Some(0x4010),
+            },
+        ];
+        assert_eq!(symbols, expected);
+    }
+
+    #[test]
+    fn invalid_number() {
+        match parse("seg\tid=0,start=0x8000,size=zz") {
+            Err(SymbolError::Parse { line: 1, message }) => assert_eq!(message, "invalid size: zz"),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+}
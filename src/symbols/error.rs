@@ -0,0 +1,51 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Error on loading a symbol file
+#[derive(Debug)]
+pub enum SymbolError {
+    /// file can't be read
+    Io(io::Error),
+    /// file name doesn't tell the format
+    UnknownFormat {
+        /// file path
+        path: String,
+    },
+    /// line can't be parsed
+    Parse {
+        /// line number starting from 1
+        line: usize,
+        /// what is wrong
+        message: String,
+    },
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolError::Io(e) => write!(f, "{}", e),
+            SymbolError::UnknownFormat { path } => write!(
+                f,
+                "{}: expected ca65 .dbg or FCEUX .nl (name.nes.ram.nl or name.nes.<bank>.nl)",
+                path
+            ),
+            SymbolError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for SymbolError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SymbolError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SymbolError {
+    fn from(e: io::Error) -> SymbolError {
+        SymbolError::Io(e)
+    }
+}
@@ -0,0 +1,99 @@
+use super::{Symbol, SymbolError};
+
+// FCEUX names bank files after 16KB PRG ROM banks
+const BANK_SIZE: usize = 0x4000;
+
+// Parse lines like `$C004#reset_handler#comment` or `$0300/10#buffer#`.
+// Lines not starting with `$` are continued comments.
+pub fn parse(text: &str, bank: Option<usize>) -> Result<Vec<Symbol>, SymbolError> {
+    let mut symbols = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let error = |message: &str| SymbolError::Parse {
+            line: i + 1,
+            message: message.to_string(),
+        };
+        let line = line.trim_end();
+        if !line.starts_with('$') {
+            continue;
+        }
+        let mut fields = line[1..].splitn(3, '#');
+        let location = fields.next().unwrap_or("");
+        let name = fields
+            .next()
+            .ok_or_else(|| error("expected '#' after address"))?;
+        if name.is_empty() {
+            // comment only
+            continue;
+        }
+        let mut location = location.splitn(2, '/');
+        let addr = location.next().unwrap_or("");
+        let addr = u16::from_str_radix(addr, 16).map_err(|_| error("invalid address"))?;
+        let size = match location.next() {
+            Some(size) => u16::from_str_radix(size, 16).map_err(|_| error("invalid size"))?,
+            None => 1,
+        };
+        let prg_offset = match bank {
+            Some(bank) if addr >= 0x8000 => Some(bank * BANK_SIZE + usize::from(addr) % BANK_SIZE),
+            _ => None,
+        };
+        symbols.push(Symbol {
+            name: name.to_string(),
+            addr,
+            size,
+            prg_offset,
+        });
+    }
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NL: &str = "\
+$0300/10#buffer#sprite buffer
+$C004#reset_handler#entry point
+continued comment
+$C010##comment only
+";
+
+    #[test]
+    fn labels_in_bank() {
+        let symbols = parse(NL, Some(3)).unwrap();
+        let expected = vec![
+            Symbol {
+                name: "buffer".to_string(),
+                addr: 0x0300,
+                size: 0x10,
+                prg_offset: None,
+            },
+            Symbol {
+                name: "reset_handler".to_string(),
+                addr: 0xc004,
+                size: 1,
+                prg_offset: Some(3 * BANK_SIZE + 0x0004),
+            },
+        ];
+        assert_eq!(symbols, expected);
+    }
+
+    #[test]
+    fn ram_labels_have_no_prg_offset() {
+        let symbols = parse(NL, None).unwrap();
+        assert!(symbols.iter().all(|symbol| symbol.prg_offset.is_none()));
+    }
+
+    #[test]
+    fn invalid_lines() {
+        for (text, expected) in &[
+            ("$zz#name#", "invalid address"),
+            ("$0300/zz#name#", "invalid size"),
+            ("$0300", "expected '#' after address"),
+        ] {
+            match parse(text, None) {
+                Err(SymbolError::Parse { line: 1, message }) => assert_eq!(&message, expected),
+                result => panic!("unexpected {:?}", result),
+            }
+        }
+    }
+}
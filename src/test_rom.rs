@@ -0,0 +1,155 @@
+use crate::cartridge::Cartridge;
+use crate::joypad::KeyState;
+use crate::Nes;
+use std::fmt;
+
+// Protocol of blargg's test ROMs.
+// See https://github.com/christopherpow/nes-test-roms/blob/master/README.md
+const STATUS_ADDR: u16 = 0x6000;
+const SIGNATURE_ADDR: u16 = 0x6001;
+const MESSAGE_ADDR: u16 = 0x6004;
+const SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET_REQUESTED: u8 = 0x81;
+// ROMs ask to press reset at least 100 msec later
+const RESET_DELAY_FRAMES: u64 = 6;
+const MAX_MESSAGE_LENGTH: u16 = 0x1000;
+
+/// Final result reported by a test ROM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestRomResult {
+    /// result code at $6000. 0 means passed
+    pub code: u8,
+    /// text at $6004
+    pub message: String,
+}
+
+impl TestRomResult {
+    /// true if the ROM reported success
+    pub fn passed(&self) -> bool {
+        self.code == 0
+    }
+}
+
+/// Error on running a test ROM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestRomError {
+    /// ROM didn't report a result in time
+    Timeout {
+        /// number of frames executed
+        frames: u64,
+        /// text at $6004 when it stopped. None if the signature was never written
+        message: Option<String>,
+    },
+    /// cpu executed KIL instruction
+    Halted {
+        /// text at $6004 when it stopped. None if the signature was never written
+        message: Option<String>,
+    },
+}
+
+impl fmt::Display for TestRomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TestRomError::Timeout { frames, message } => {
+                write!(f, "no result after {} frames", frames)?;
+                if let Some(message) = message {
+                    write!(f, ": {}", message)?;
+                }
+                Ok(())
+            }
+            TestRomError::Halted { message } => {
+                write!(f, "cpu halted")?;
+                if let Some(message) = message {
+                    write!(f, ": {}", message)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TestRomError {}
+
+/// Runs a test ROM using the $6000 status protocol until it reports a result
+pub struct TestRomRunner {
+    nes: Nes,
+    max_frames: u64,
+}
+
+impl TestRomRunner {
+    /// Create runner which gives up after `max_frames` frames
+    pub fn new(cartridge: Cartridge, max_frames: u64) -> TestRomRunner {
+        TestRomRunner {
+            nes: Nes::with_cartridge(cartridge),
+            max_frames,
+        }
+    }
+
+    /// Run until the ROM reports a result. Reset is pressed when the ROM requests it.
+    pub fn run(&mut self) -> Result<TestRomResult, TestRomError> {
+        let mut reset_at = None;
+        for frame in 0..self.max_frames {
+            self.nes.next_frame(KeyState::default());
+            if self.nes.is_cpu_halted() {
+                return Err(TestRomError::Halted {
+                    message: self.message(),
+                });
+            }
+            if !self.has_signature() {
+                continue;
+            }
+            match self.status() {
+                STATUS_RUNNING => {}
+                STATUS_RESET_REQUESTED => match reset_at {
+                    None => reset_at = Some(frame + RESET_DELAY_FRAMES),
+                    Some(at) if frame >= at => {
+                        self.nes.reset();
+                        reset_at = None;
+                    }
+                    Some(_) => {}
+                },
+                code if code < STATUS_RUNNING => {
+                    return Ok(TestRomResult {
+                        code,
+                        message: self.message().unwrap_or_default(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        Err(TestRomError::Timeout {
+            frames: self.max_frames,
+            message: self.message(),
+        })
+    }
+
+    /// Emulator running the ROM
+    pub fn nes(&self) -> &Nes {
+        &self.nes
+    }
+
+    fn load(&self, addr: u16) -> u8 {
+        self.nes.cartridge().cpu_load(addr).unwrap_or(0)
+    }
+
+    fn status(&self) -> u8 {
+        self.load(STATUS_ADDR)
+    }
+
+    fn has_signature(&self) -> bool {
+        (0..SIGNATURE.len() as u16).all(|i| self.load(SIGNATURE_ADDR + i) == SIGNATURE[i as usize])
+    }
+
+    // zero-terminated text at $6004
+    fn message(&self) -> Option<String> {
+        if !self.has_signature() {
+            return None;
+        }
+        let bytes = (0..MAX_MESSAGE_LENGTH)
+            .map(|i| self.load(MESSAGE_ADDR + i))
+            .take_while(|&b| b != 0)
+            .collect::<Vec<u8>>();
+        Some(String::from_utf8_lossy(&bytes).trim().to_string())
+    }
+}
@@ -0,0 +1,163 @@
+use crate::cpu::Registers;
+use crate::disasm::{disassemble, DisassembledInstruction, MemoryView, Operand};
+use crate::symbols::SymbolTable;
+use crate::Nes;
+use log::warn;
+use std::cell::RefCell;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+// Memory accessed by the instruction before it executes, as nestest logs it, e.g.
+// ` = 00`, ` @ 0300 = 89`, ` = 0200 @ 0200 = 5A` or ` = DB7E` for `JMP ($0200)`
+fn annotation(inst: &DisassembledInstruction, reg: &Registers, memory: &impl MemoryView) -> String {
+    let mut resolved = inst.clone();
+    resolved.resolve(reg, memory);
+    let addr = match resolved.effective_addr {
+        Some(addr) => addr,
+        None => return String::new(),
+    };
+    // APU and I/O registers print as FF like the reference logs
+    let value = match addr {
+        0x4000..=0x401f => 0xff,
+        _ => memory.peek(addr),
+    };
+    match inst.operand {
+        Operand::Absolute(_) if inst.mnemonic == "JMP" || inst.mnemonic == "JSR" => String::new(),
+        Operand::Relative(_) => String::new(),
+        Operand::ZeroPage(_) | Operand::Absolute(_) => format!(" = {:02X}", value),
+        Operand::ZeroPageX(_) | Operand::ZeroPageY(_) => {
+            format!(" @ {:02X} = {:02X}", addr, value)
+        }
+        Operand::AbsoluteX(_) | Operand::AbsoluteY(_) => {
+            format!(" @ {:04X} = {:02X}", addr, value)
+        }
+        Operand::Indirect(_) => format!(" = {:04X}", addr),
+        Operand::IndirectX(pointer) => format!(
+            " @ {:02X} = {:04X} = {:02X}",
+            pointer.wrapping_add(reg.x),
+            addr,
+            value
+        ),
+        Operand::IndirectY(_) => format!(
+            " = {:04X} @ {:04X} = {:02X}",
+            addr.wrapping_sub(u16::from(reg.y)),
+            addr,
+            value
+        ),
+        Operand::Implied | Operand::Accumulator | Operand::Immediate(_) => String::new(),
+    }
+}
+
+/// Writes one line per executed instruction in nestest log format, e.g.
+/// `C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7`
+#[derive(Clone)]
+pub struct TraceLogger {
+    writer: Rc<RefCell<dyn Write>>,
+    pc_range: Option<RangeInclusive<u16>>,
+    symbols: Option<Rc<SymbolTable>>,
+}
+
+impl TraceLogger {
+    /// Create logger writing into the writer
+    pub fn new<W: Write + 'static>(writer: W) -> TraceLogger {
+        TraceLogger {
+            writer: Rc::new(RefCell::new(writer)),
+            pc_range: None,
+            symbols: None,
+        }
+    }
+
+    /// Log only instructions whose address is in the range
+    pub fn set_pc_range(&mut self, range: RangeInclusive<u16>) {
+        self.pc_range = Some(range);
+    }
+
+    /// Log all instructions
+    pub fn clear_pc_range(&mut self) {
+        self.pc_range = None;
+    }
+
+    /// Print operand addresses as labels. Lines no longer match reference logs.
+    pub fn set_symbols(&mut self, symbols: Rc<SymbolTable>) {
+        self.symbols = Some(symbols);
+    }
+
+    /// True if the instruction at the address should be logged
+    pub fn accepts(&self, pc: u16) -> bool {
+        match &self.pc_range {
+            Some(range) => range.contains(&pc),
+            None => true,
+        }
+    }
+
+    /// Format a line. Unofficial opcodes are marked with `*` like nestest.
+    /// Memory the instruction accesses is read from `memory` before it executes.
+    pub fn format_line(
+        inst: &DisassembledInstruction,
+        reg: &Registers,
+        memory: &impl MemoryView,
+        scanline: u16,
+        dot: u16,
+        cycles: u64,
+    ) -> String {
+        TraceLogger::format_line_with(inst, &|_| None, reg, memory, scanline, dot, cycles)
+    }
+
+    /// Format a line with a label function used for addresses in the operand
+    pub fn format_line_with(
+        inst: &DisassembledInstruction,
+        label: &dyn Fn(u16) -> Option<String>,
+        reg: &Registers,
+        memory: &impl MemoryView,
+        scanline: u16,
+        dot: u16,
+        cycles: u64,
+    ) -> String {
+        let bytes = inst
+            .bytes
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!(
+            "{:04X}  {:<8} {}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+            inst.addr,
+            bytes,
+            if inst.unofficial { '*' } else { ' ' },
+            inst.format_with(label) + &annotation(inst, reg, memory),
+            reg.a,
+            reg.x,
+            reg.y,
+            reg.p,
+            reg.s,
+            scanline,
+            dot,
+            cycles
+        )
+    }
+
+    /// Write a line for the instruction about to be executed
+    pub fn log(&self, nes: &Nes) {
+        let reg = nes.cpu_registers();
+        let inst = disassemble(nes, reg.pc);
+        let (scanline, dot) = nes.ppu_position();
+        let label = |addr| {
+            self.symbols
+                .as_ref()
+                .and_then(|symbols| symbols.label(addr, nes))
+        };
+        let line =
+            TraceLogger::format_line_with(&inst, &label, &reg, nes, scanline, dot, nes.cycles());
+        if let Err(e) = writeln!(self.writer.borrow_mut(), "{}", line) {
+            warn!("failed to write trace: {}", e);
+        }
+    }
+
+    /// Flush the writer
+    pub fn flush(&self) {
+        if let Err(e) = self.writer.borrow_mut().flush() {
+            warn!("failed to flush trace: {}", e);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Runs blargg's test ROMs which report results at $6000.
+//!
+//! The ROMs are not included, so the tests are ignored by default. Fetch the suites from
+//! https://github.com/christopherpow/nes-test-roms into `tests/roms/` with
+//! `scripts/fetch-test-roms.sh` and run `cargo test -- --ignored`. A missing ROM fails the test.
+
+use nes::test_rom::TestRomRunner;
+use nes::Cartridge;
+use std::fs;
+use std::path::PathBuf;
+
+// 20 seconds
+const MAX_FRAMES: u64 = 20 * 60;
+
+fn run_test_rom(path: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/roms")
+        .join(path);
+    let rom = fs::read(&path).unwrap_or_else(|e| {
+        panic!(
+            "{:?}: {}. Run scripts/fetch-test-roms.sh to download the test ROMs.",
+            path, e
+        )
+    });
+    let cartridge = Cartridge::parse_file(&rom).unwrap();
+    match TestRomRunner::new(cartridge, MAX_FRAMES).run() {
+        Ok(result) => assert!(
+            result.passed(),
+            "{:?} failed with code {}\n{}",
+            path,
+            result.code,
+            result.message
+        ),
+        Err(e) => panic!("{:?}: {}", path, e),
+    }
+}
+
+macro_rules! test_roms {
+    ($($name:ident => $path:expr,)*) => {
+        $(
+            #[test]
+            #[ignore]
+            fn $name() {
+                run_test_rom($path);
+            }
+        )*
+    };
+}
+
+test_roms! {
+    instr_test_01_basics => "instr_test-v5/rom_singles/01-basics.nes",
+    instr_test_02_implied => "instr_test-v5/rom_singles/02-implied.nes",
+    instr_test_03_immediate => "instr_test-v5/rom_singles/03-immediate.nes",
+    instr_test_04_zero_page => "instr_test-v5/rom_singles/04-zero_page.nes",
+    instr_test_05_zp_xy => "instr_test-v5/rom_singles/05-zp_xy.nes",
+    instr_test_06_absolute => "instr_test-v5/rom_singles/06-absolute.nes",
+    instr_test_07_abs_xy => "instr_test-v5/rom_singles/07-abs_xy.nes",
+    instr_test_08_ind_x => "instr_test-v5/rom_singles/08-ind_x.nes",
+    instr_test_09_ind_y => "instr_test-v5/rom_singles/09-ind_y.nes",
+    instr_test_10_branches => "instr_test-v5/rom_singles/10-branches.nes",
+    instr_test_11_stack => "instr_test-v5/rom_singles/11-stack.nes",
+    instr_test_12_jmp_jsr => "instr_test-v5/rom_singles/12-jmp_jsr.nes",
+    instr_test_13_rts => "instr_test-v5/rom_singles/13-rts.nes",
+    instr_test_14_rti => "instr_test-v5/rom_singles/14-rti.nes",
+    instr_test_15_brk => "instr_test-v5/rom_singles/15-brk.nes",
+    instr_test_16_special => "instr_test-v5/rom_singles/16-special.nes",
+    ppu_vbl_nmi_01_vbl_basics => "ppu_vbl_nmi/rom_singles/01-vbl_basics.nes",
+    ppu_vbl_nmi_02_vbl_set_time => "ppu_vbl_nmi/rom_singles/02-vbl_set_time.nes",
+    ppu_vbl_nmi_03_vbl_clear_time => "ppu_vbl_nmi/rom_singles/03-vbl_clear_time.nes",
+    ppu_vbl_nmi_04_nmi_control => "ppu_vbl_nmi/rom_singles/04-nmi_control.nes",
+    ppu_vbl_nmi_05_nmi_timing => "ppu_vbl_nmi/rom_singles/05-nmi_timing.nes",
+    ppu_vbl_nmi_06_suppression => "ppu_vbl_nmi/rom_singles/06-suppression.nes",
+    ppu_vbl_nmi_07_nmi_on_timing => "ppu_vbl_nmi/rom_singles/07-nmi_on_timing.nes",
+    ppu_vbl_nmi_08_nmi_off_timing => "ppu_vbl_nmi/rom_singles/08-nmi_off_timing.nes",
+    ppu_vbl_nmi_09_even_odd_frames => "ppu_vbl_nmi/rom_singles/09-even_odd_frames.nes",
+    ppu_vbl_nmi_10_even_odd_timing => "ppu_vbl_nmi/rom_singles/10-even_odd_timing.nes",
+    apu_test_1_len_ctr => "apu_test/rom_singles/1-len_ctr.nes",
+    apu_test_2_len_table => "apu_test/rom_singles/2-len_table.nes",
+    apu_test_3_irq_flag => "apu_test/rom_singles/3-irq_flag.nes",
+    apu_test_4_jitter => "apu_test/rom_singles/4-jitter.nes",
+    apu_test_5_len_timing => "apu_test/rom_singles/5-len_timing.nes",
+    apu_test_6_irq_flag_timing => "apu_test/rom_singles/6-irq_flag_timing.nes",
+    apu_test_7_dmc_basics => "apu_test/rom_singles/7-dmc_basics.nes",
+    apu_test_8_dmc_rates => "apu_test/rom_singles/8-dmc_rates.nes",
+    sprite_hit_01_basics => "ppu_sprite_hit/rom_singles/01-basics.nes",
+    sprite_hit_02_alignment => "ppu_sprite_hit/rom_singles/02-alignment.nes",
+    sprite_hit_03_corners => "ppu_sprite_hit/rom_singles/03-corners.nes",
+    sprite_hit_04_flip => "ppu_sprite_hit/rom_singles/04-flip.nes",
+    sprite_hit_05_left_clip => "ppu_sprite_hit/rom_singles/05-left_clip.nes",
+    sprite_hit_06_right_edge => "ppu_sprite_hit/rom_singles/06-right_edge.nes",
+    sprite_hit_07_screen_bottom => "ppu_sprite_hit/rom_singles/07-screen_bottom.nes",
+    sprite_hit_08_double_height => "ppu_sprite_hit/rom_singles/08-double_height.nes",
+    sprite_hit_09_timing => "ppu_sprite_hit/rom_singles/09-timing.nes",
+    sprite_hit_10_timing_order => "ppu_sprite_hit/rom_singles/10-timing_order.nes",
+}
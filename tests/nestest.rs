@@ -0,0 +1,63 @@
+//! Runs nestest.nes in automation mode and compares every trace line with the golden log.
+//!
+//! The ROM and log are not included, so the test is ignored by default. Fetch `nestest.nes`
+//! and `nestest.log` from http://www.qmtpro.com/~nes/misc/ into `tests/roms/` with
+//! `scripts/fetch-test-roms.sh` and run `cargo test -- --ignored`.
+
+use nes::disasm::disassemble;
+use nes::trace::TraceLogger;
+use nes::{Cartridge, Nes};
+use std::fs;
+use std::path::Path;
+
+fn read_file(path: &Path) -> Vec<u8> {
+    fs::read(path).unwrap_or_else(|e| {
+        panic!(
+            "{:?}: {}. Run scripts/fetch-test-roms.sh to download the test ROMs.",
+            path, e
+        )
+    })
+}
+
+// Line the trace logger writes for the instruction about to be executed
+fn current_line(nes: &Nes) -> String {
+    let reg = nes.cpu_registers();
+    let (scanline, dot) = nes.ppu_position();
+    let inst = disassemble(nes, reg.pc);
+    TraceLogger::format_line(&inst, &reg, nes, scanline, dot, nes.cycles())
+}
+
+// Marks the columns where two lines differ
+fn diff_marker(expected: &str, actual: &str) -> String {
+    expected
+        .chars()
+        .zip(actual.chars().chain(std::iter::repeat(' ')))
+        .map(|(e, a)| if e == a { ' ' } else { '^' })
+        .collect()
+}
+
+#[test]
+#[ignore]
+fn nestest_matches_golden_log() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms");
+    let rom = read_file(&dir.join("nestest.nes"));
+    let log = String::from_utf8(read_file(&dir.join("nestest.log"))).unwrap();
+
+    let mut nes = Nes::with_cartridge(Cartridge::parse_file(&rom).unwrap());
+    // automation mode
+    nes.set_pc(0xc000);
+
+    for (number, expected) in log.lines().enumerate() {
+        let actual = current_line(&nes);
+        if expected != actual {
+            panic!(
+                "diverged at line {}\nexpected: {}\nactual:   {}\n          {}",
+                number + 1,
+                expected,
+                actual,
+                diff_marker(expected, &actual)
+            );
+        }
+        nes.step();
+    }
+}